@@ -1 +1,26 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod user;
+
+use actix_web::HttpResponse;
+use lib_sql::traits::{Error, SqlErrorKind};
+
+use crate::JsonResult;
+
+// 将数据库错误转为响应：唯一约束冲突与并发修改冲突返回 409，数据库忙返回 503，数据不存在返回 404，其它返回 500
+pub fn db_error(e: &Error) -> HttpResponse {
+    match e.sql_kind() {
+        Some(SqlErrorKind::UniqueViolation { columns, .. }) => HttpResponse::Conflict().json(
+            JsonResult::<()>::error(&format!("{} already exists", columns.join(", "))),
+        ),
+        Some(SqlErrorKind::Busy | SqlErrorKind::Locked) => HttpResponse::ServiceUnavailable()
+            .json(JsonResult::<()>::error("Database is busy, please retry")),
+        _ if e.is_conflict() => HttpResponse::Conflict().json(JsonResult::<()>::error(
+            "Data was modified concurrently, please reload and retry",
+        )),
+        _ if e.is_not_found() => {
+            HttpResponse::NotFound().json(JsonResult::<()>::error(&e.to_string()))
+        }
+        _ => HttpResponse::InternalServerError()
+            .json(JsonResult::<()>::error(&format!("Database error: {}", e))),
+    }
+}
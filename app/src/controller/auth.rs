@@ -5,8 +5,14 @@ use actix_web::{HttpRequest, HttpResponse, Responder, web};
 use lib_date::Calendar;
 use serde::{Deserialize, Serialize};
 
-use crate::{JsonResult, utils::auth::create_jwt, model::User, utils::auth::*};
-use lib_sql::{sources::Dao, traits::CommInterface};
+use crate::{
+    AppState, JsonResult, controller::db_error, utils::auth::create_jwt, model::User,
+    utils::auth::*,
+};
+use lib_sql::{
+    traits::{CommInterface, TransactionMode},
+    utils::{Matcher, Operator},
+};
 
 // 结构体
 #[derive(Debug, Deserialize)]
@@ -78,32 +84,24 @@ impl From<User> for UserResponse {
 }
 
 // 1.1 用户登录
-pub async fn handle_login(req: web::Json<LoginRequest>) -> impl Responder {
-    let dao = match Dao::new() {
-        Ok(dao) => dao,
-        Err(e) => {
+pub async fn handle_login(
+    state: web::Data<AppState>,
+    req: web::Json<LoginRequest>,
+) -> impl Responder {
+    // 查找用户
+    let mut matcher = Matcher::new();
+    matcher.and("username", Operator::Eq, &req.username);
+    let user = match state.dao.find_one::<User>(matcher).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
             return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
+                .json(JsonResult::<()>::error("Invalid username"));
         }
-    };
-
-    // 查找用户
-    let users = match dao.list_all::<User>() {
-        Ok(users) => users,
         Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
+            return db_error(&e);
         }
     };
 
-    let user = users.iter().find(|u| u.username == req.username);
-    if user.is_none() {
-        return HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error("Invalid username"));
-    }
-
-    let user = user.unwrap();
-
     // 验证密码
     if !verify_password(&req.password, &user.password) {
         return HttpResponse::InternalServerError()
@@ -113,7 +111,7 @@ pub async fn handle_login(req: web::Json<LoginRequest>) -> impl Responder {
     // 生成 token 并返回用户信息
     let token = create_jwt(&user.id, req.remember);
     let response = LoginResponse {
-        user: UserResponse::from(user.clone()),
+        user: UserResponse::from(user),
         token,
     };
 
@@ -121,54 +119,48 @@ pub async fn handle_login(req: web::Json<LoginRequest>) -> impl Responder {
 }
 
 // 1.2 用户注册
-pub async fn handle_register(req: web::Json<RegisterRequest>) -> impl Responder {
+pub async fn handle_register(
+    state: web::Data<AppState>,
+    req: web::Json<RegisterRequest>,
+) -> impl Responder {
     // 验证密码一致性
     if req.password != req.confirm_password {
         return HttpResponse::BadRequest().json(JsonResult::<()>::error("Passwords do not match"));
     }
 
-    let dao = match Dao::new() {
-        Ok(dao) => dao,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
-        }
-    };
-
-    // 检查用户名是否已存在
-    let users = match dao.list_all::<User>() {
-        Ok(users) => users,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
-        }
-    };
-
-    if users.iter().any(|u| u.username == req.username) {
-        return HttpResponse::Conflict().json(JsonResult::<()>::error("Username already exists"));
-    }
-
-    if users.iter().any(|u| u.email == req.email) {
-        return HttpResponse::Conflict().json(JsonResult::<()>::error("Email already exists"));
-    }
-
     // 创建新用户
-    let mut new_user = User {
+    let new_user = User {
         id: 0, // 数据库会自动生成
         username: req.username.clone(),
         password: hash_password(&req.password),
         email: req.email.clone(),
-        created_at: Calendar::now().timestamp() as i32,
+        ..Default::default()
     };
 
-    match dao.add(new_user.clone()) {
-        Ok(_) => {
-            // 获取新创建的用户 ID
-            let users = dao.list_all::<User>().unwrap_or_default();
-            if let Some(user) = users.iter().find(|u| u.username == req.username) {
-                new_user.id = user.id;
-            }
+    // 检查与写入放在同一个写事务中，避免并发注册出现重复用户
+    let (username, email) = (req.username.clone(), req.email.clone());
+    let result = state.dao.transaction_with(TransactionMode::Immediate, move |tx| {
+        // 检查用户名与邮箱是否已存在，已删除的用户仍占用，以便恢复
+        let mut matcher = Matcher::new();
+        matcher.with_deleted().and("username", Operator::Eq, username);
+        if tx.exists::<User>(&matcher)? {
+            return Ok(Err("Username already exists"));
+        }
+        let mut matcher = Matcher::new();
+        matcher.with_deleted().and("email", Operator::Eq, email);
+        if tx.exists::<User>(&matcher)? {
+            return Ok(Err("Email already exists"));
+        }
 
+        // 写入并读回新创建的用户，拿到自增的 ID
+        // 用户名与邮箱另有唯一索引兜底，插入冲突时由 db_error 返回 409
+        let user = tx.insert_and_reload(new_user)?;
+        Ok(Ok(user))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(new_user)) => {
             let token = generate_token(new_user.id);
             let response = LoginResponse {
                 user: UserResponse::from(new_user),
@@ -177,40 +169,24 @@ pub async fn handle_register(req: web::Json<RegisterRequest>) -> impl Responder
 
             HttpResponse::Ok().json(JsonResult::success(response))
         }
-        Err(e) => HttpResponse::InternalServerError().json(JsonResult::<()>::error(&format!(
-            "Failed to create user: {:?}",
-            e
-        ))),
+        Ok(Err(message)) => HttpResponse::Conflict().json(JsonResult::<()>::error(message)),
+        Err(e) => db_error(&e),
     }
 }
 
 // 1.3 获取当前用户信息
-pub async fn handle_me(user: User) -> impl Responder {
+pub async fn handle_me(state: web::Data<AppState>, user: User) -> impl Responder {
     // 从中间件中获取当前用户 ID
     let user_id = user.id;
 
-    let dao = match Dao::new() {
-        Ok(dao) => dao,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
-        }
-    };
-
-    let result = dao.list_all::<User>();
-    match result {
-        Ok(list) => {
-            // 使用从 token 中解析出来的用户 ID
-            let user = list.iter().find(|u| u.id == user_id);
-            if user.is_none() {
-                return HttpResponse::NotFound().json(JsonResult::<()>::error("User not found"));
-            }
-            let user = user.unwrap().clone();
+    // 使用从 token 中解析出来的用户 ID
+    match state.dao.get::<User, _>(user_id).await {
+        Ok(Some(user)) => {
             let user_response = UserResponse::from(user);
             HttpResponse::Ok().json(JsonResult::success(user_response))
         }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Database error: {:?}", e))),
+        Ok(None) => HttpResponse::NotFound().json(JsonResult::<()>::error("User not found")),
+        Err(e) => db_error(&e),
     }
 }
 
@@ -228,7 +204,7 @@ pub async fn handle_refresh(user: User) -> impl Responder {
 }
 
 // 1.6 验证 Token
-pub async fn handle_verify(req: HttpRequest) -> impl Responder {
+pub async fn handle_verify(state: web::Data<AppState>, req: HttpRequest) -> impl Responder {
     // 从 header 中获取 token，Authorization: Bearer <token>
     let auth_header = match req.headers().get("Authorization") {
         Some(header) => header,
@@ -288,25 +264,16 @@ pub async fn handle_verify(req: HttpRequest) -> impl Responder {
     }
 
     // 验证用户是否存在
-    let dao = match Dao::new() {
-        Ok(dao) => dao,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
+    let mut matcher = Matcher::new();
+    matcher.and("id", Operator::Eq, user_id);
+    match state.dao.exists::<User>(matcher).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Unauthorized().json(JsonResult::<()>::error("Invalid user"));
         }
-    };
-
-    let users = match dao.list_all::<User>() {
-        Ok(users) => users,
         Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
+            return db_error(&e);
         }
-    };
-
-    let user = users.iter().find(|u| u.id == user_id);
-    if user.is_none() {
-        return HttpResponse::Unauthorized().json(JsonResult::<()>::error("Invalid user"));
     }
 
     // Token 验证成功，返回原 token（或生成新的 token）
@@ -318,48 +285,34 @@ pub async fn handle_verify(req: HttpRequest) -> impl Responder {
 
 // 1.7 修改密码
 pub async fn handle_change_password(
+    state: web::Data<AppState>,
     req: web::Json<ChangePasswordRequest>,
     user: User,
 ) -> impl Responder {
-    let dao = match Dao::new() {
-        Ok(dao) => dao,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
-        }
-    };
-
     // 获取当前用户
-    let users = match dao.list_all::<User>() {
-        Ok(users) => users,
+    let user = match state.dao.get::<User, _>(user.id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(JsonResult::<()>::error("User not found"));
+        }
         Err(e) => {
-            return HttpResponse::InternalServerError()
-                .json(JsonResult::<()>::error(&format!("Database error: {:?}", e)));
+            return db_error(&e);
         }
     };
 
-    let user = users.iter().find(|u| u.id == user.id);
-    if user.is_none() {
-        return HttpResponse::NotFound().json(JsonResult::<()>::error("User not found"));
-    }
-
-    let user = user.unwrap();
-
     // 验证旧密码
     if !verify_password(&req.old_password, &user.password) {
         return HttpResponse::InternalServerError().json(JsonResult::<()>::error("旧密码不正确"));
     }
 
-    // 更新密码
-    let mut updated_user = user.clone();
-    updated_user.password = hash_password(&req.new_password);
-
-    match dao.set(updated_user.clone()) {
+    // 按读取时的版本号修改，期间被并发修改时返回 409
+    let user = User {
+        password: hash_password(&req.new_password),
+        ..user
+    };
+    match state.dao.set(user).await {
         Ok(_) => HttpResponse::Ok().json(JsonResult::<()>::default()),
-        Err(e) => HttpResponse::InternalServerError().json(JsonResult::<()>::error(&format!(
-            "Failed to update password: {:?}",
-            e
-        ))),
+        Err(e) => db_error(&e),
     }
 }
 
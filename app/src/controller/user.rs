@@ -0,0 +1,49 @@
+// 用户管理接口
+
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use lib_sql::filter::SearchSpec;
+
+use crate::{
+    AppState, JsonResult,
+    controller::{auth::UserResponse, db_error},
+    model::{Page, User},
+};
+
+// 2.1 用户列表，支持过滤、排序与分页：
+// /api/users?filter[username][like]=de&sort=-created_at&page=1&size=20
+// 按用户名与邮箱全文检索：/api/users?q=demo
+// 也可以使用上一页返回的 next_cursor 翻页：/api/users?sort=-created_at&after=<next_cursor>&count=false
+pub async fn handle_user_list(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    _user: User,
+) -> impl Responder {
+    let search = match SearchSpec::from_query(req.query_string())
+        .and_then(|spec| spec.to_search::<User>())
+    {
+        Ok(search) => search,
+        Err(e) => {
+            return HttpResponse::BadRequest()
+                .json(JsonResult::<()>::error(&format!("Invalid query: {}", e)));
+        }
+    };
+
+    match state.dao.list::<User>(search).await {
+        Ok((list, search)) => {
+            let size = search.limit as usize;
+            let page = Page {
+                list: list.into_iter().map(UserResponse::from).collect(),
+                total: search.total,
+                // 游标翻页时忽略 start，没有对应的页码
+                page: match search.cursor {
+                    Some(_) => None,
+                    None => Some(search.start as usize / size + 1),
+                },
+                size,
+                next_cursor: search.next_cursor,
+            };
+            HttpResponse::Ok().json(JsonResult::success(page))
+        }
+        Err(e) => db_error(&e),
+    }
+}
@@ -7,18 +7,21 @@ use lib_log::log;
 
 mod controller;
 mod model;
+mod tests;
 mod utils;
 
-use controller::auth::*;
-use lib_sql::utils::read_config;
+use controller::{auth::*, user::*};
+use lib_sql::{async_dao::AsyncDao, pool::Pool, utils::read_config};
 use model::*;
-use serde::{Deserialize, Serialize};
 
 use crate::utils::table;
 
 // 应用状态
-#[derive(Clone, Debug, Deserialize, Serialize, Default)]
-struct AppState {}
+#[derive(Clone)]
+struct AppState {
+    // 异步的数据库访问，所有请求共用同一个连接池
+    dao: AsyncDao,
+}
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
@@ -28,20 +31,45 @@ async fn main() -> io::Result<()> {
     }
     env_logger::init_from_env(Env::default().default_filter_or("info"));
 
-    // 初始化数据库
-    if let Err(e) = table::init() {
-        log::log_err(&format!("init database error: {:?}", e));
-        return Err(io::Error::new(io::ErrorKind::Other, "init database error"));
-    };
-
-    
     let config = read_config("./conf/config.toml");
     if let Err(e) = config {
-        log::log_err(&format!("read config error: {:?}", e));
-        return Err(io::Error::new(io::ErrorKind::Other, "read config error"));
+        log::log_err(&format!("read config error: {}", e));
+        return Err(io::Error::other("read config error"));
     }
     let config = config.unwrap();
 
+    // 创建数据库连接池
+    let pool = match Pool::new(config.clone()) {
+        Ok(pool) => pool,
+        Err(e) => {
+            log::log_err(&format!("create database pool error: {}", e));
+            return Err(io::Error::other("create database pool error"));
+        }
+    };
+
+    // 迁移命令：app migrate status | app migrate rollback <version>
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(|s| s.as_str()) == Some("migrate") {
+        return table::migrate_command(&pool, &args[1..]).map_err(|e| {
+            log::log_err(&format!("migrate error: {}", e));
+            io::Error::other("migrate error")
+        });
+    }
+
+    // 维护命令：app db backup | app db restore <file> | app db vacuum ...
+    if args.first().map(|s| s.as_str()) == Some("db") {
+        return table::db_command(&pool, &args[1..]).map_err(|e| {
+            log::log_err(&format!("db command error: {}", e));
+            io::Error::other("db command error")
+        });
+    }
+
+    // 初始化数据库，并检查实体与表结构是否一致
+    if let Err(e) = table::init(&pool, config.schema_check.unwrap_or_default()) {
+        log::log_err(&format!("init database error: {}", e));
+        return Err(io::Error::other("init database error"));
+    };
+
     // 静态文件目录
     let web_dir = config.webdir.unwrap_or("./web".to_string());
     let port = config.port.unwrap_or(3000);
@@ -49,6 +77,11 @@ async fn main() -> io::Result<()> {
     // 启动定时任务
     // job::account::start();
 
+    // 数据库操作在专用线程中执行，不阻塞 actix 的工作线程
+    let state = web::Data::new(AppState {
+        dao: AsyncDao::new(pool),
+    });
+
     let ip = "0.0.0.0";
     log::log_info(&format!("Server started on http://{}:{}", ip, port));
     // 启动 HTTP 服务
@@ -72,11 +105,13 @@ async fn main() -> io::Result<()> {
                 "/api/auth/reset-password",
                 web::post().to(handle_reset_password),
             )
+            // 用户管理路由
+            .route("/api/users", web::get().to(handle_user_list))
             // 心跳路由
             .route("/api/ping", web::route().to(handle_ping))
             .wrap(Logger::default()) // 日志记录中间件
             .app_data(web::JsonConfig::default().error_handler(handle_server_error)) //
-            .app_data(web::Data::new(AppState {}))
+            .app_data(state.clone())
             .service(actix_files::Files::new("/", &web_dir).index_file("index.html"))
             .default_service(web::get().to(handle_all_others))
     })
@@ -1,7 +1,4 @@
-use lib_json::object::JsonObject;
-use lib_sql::{
-    traits::{Error, Table},
-};
+use lib_sql::traits::Table;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -34,70 +31,40 @@ impl<T: Serialize> JsonResult<T> {
     }
 }
 
-// 定义 User 结构体
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+// 分页列表，next_cursor 用于获取下一页，为空时表示没有更多数据；使用游标翻页时 page 为空
+#[derive(Debug, Serialize, Clone)]
+pub struct Page<T: Serialize> {
+    pub list: Vec<T>,
+    pub total: i64,
+    pub page: Option<usize>,
+    pub size: usize,
+    pub next_cursor: Option<String>,
+}
+
+// 定义 User 结构体，列表接口允许按 filterable 的字段过滤、按 sortable 的字段排序
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Table)]
+#[table(name = "users")]
 pub struct User {
+    #[id(auto_increment)]
+    #[column(sortable)]
     pub id: i32,
+    #[column(filterable, sortable, searchable)]
     pub username: String,
     pub password: String,
+    #[column(filterable, searchable)]
     pub email: String,
+    // 创建时间与修改时间由 lib-sql 在写入时自动填写
+    #[column(filterable, sortable, created_at)]
     pub created_at: i32,
-}
-
-impl Table for User {
-    fn id() -> &'static str {
-        "id"
-    }
-
-    fn id_auto_increase() -> bool {
-        true
-    }
-
-    fn columns() -> Vec<&'static str> {
-        vec!["id", "username", "password", "email", "created_at"]
-    }
-
-    fn id_value(&self) -> String {
-        self.id.to_string()
-    }
-
-    fn table_name() -> &'static str {
-        "user"
-    }
-
-    fn from_json_object(row: &JsonObject) -> Result<Self, Error> {
-        let mut entity = Self::default();
-        match row.get_i32("id") {
-            Some(v) => entity.id = v,
-            None => return Err(Error::ParseError),
-        }
-        match row.get_str("username") {
-            Some(v) => entity.username = v.to_string(),
-            None => return Err(Error::ParseError),
-        }
-        match row.get_str("password") {
-            Some(v) => entity.password = v.to_string(),
-            None => return Err(Error::ParseError),
-        }
-        match row.get_str("email") {
-            Some(v) => entity.email = v.to_string(),
-            None => return Err(Error::ParseError),
-        }
-        match row.get_i32("created_at") {
-            Some(v) => entity.created_at = v,
-            None => return Err(Error::ParseError),
-        }
-        Ok(entity)
-    }
-
-    fn to_json_object(&self) -> Result<JsonObject, Error> {
-        let mut obj = JsonObject::new();
-        obj.set_i32("id", self.id);
-        obj.set_str("username", &self.username);
-        obj.set_str("password", &self.password);
-        obj.set_str("email", &self.email);
-        obj.set_i32("created_at", self.created_at);
-        Ok(obj)
-    }
-    
+    #[serde(default)]
+    #[column(updated_at)]
+    pub updated_at: i64,
+    // 版本号，修改时检查，避免并发修改相互覆盖
+    #[serde(default)]
+    #[column(version)]
+    pub version: i64,
+    // 删除时间，删除的用户可以恢复
+    #[serde(default)]
+    #[column(soft_delete)]
+    pub deleted_at: Option<i64>,
 }
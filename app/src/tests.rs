@@ -0,0 +1,161 @@
+#![cfg(test)]
+
+use actix_web::{
+    App,
+    http::StatusCode,
+    test::{TestRequest, call_and_read_body_json, call_service, init_service},
+    web,
+};
+use lib_sql::{
+    async_dao::AsyncDao,
+    pool::Pool,
+    schema::SchemaCheckMode,
+    traits::{CommInterface, SqlErrorKind},
+    transfer::Format,
+    utils::Config,
+};
+use serde_json::{Value, json};
+
+use crate::{
+    AppState,
+    controller::{
+        auth::{handle_login, handle_register},
+        db_error,
+        user::handle_user_list,
+    },
+    model::User,
+    utils::table,
+};
+
+// 测试用的用户，密码为 demo123
+const USERS: &str = r#"[
+{"id":2,"username":"alice","password":"62cc2d8b4bf2d8728120d052163a77df","email":"alice@example.com","created_at":1700000000},
+{"id":3,"username":"bob","password":"62cc2d8b4bf2d8728120d052163a77df","email":"bob@example.com","created_at":1700000000}
+]"#;
+
+// 内存数据库的连接池，已执行迁移并添加默认用户，不读取配置文件
+fn memory_pool() -> Pool {
+    let pool = Pool::new(Config::memory()).unwrap();
+    table::init(&pool, SchemaCheckMode::Strict).unwrap();
+    pool
+}
+
+#[test]
+fn test_init() {
+    let pool = memory_pool();
+    // 再次初始化不重复执行迁移，也不重复添加默认用户
+    table::init(&pool, SchemaCheckMode::Strict).unwrap();
+    let users = pool.get().unwrap().list_all::<User>().unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].username, "demo");
+}
+
+#[actix_web::test]
+async fn test_login_and_user_list() {
+    let pool = memory_pool();
+    pool.get()
+        .unwrap()
+        .load_fixture("users", USERS, Format::Json)
+        .unwrap();
+    let state = web::Data::new(AppState {
+        dao: AsyncDao::new(pool),
+    });
+    let app = init_service(
+        App::new()
+            .app_data(state)
+            .route("/api/auth/login", web::post().to(handle_login))
+            .route("/api/users", web::get().to(handle_user_list)),
+    )
+    .await;
+
+    let login = |password: &str| {
+        TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({"username": "alice", "password": password, "remember": false}))
+            .to_request()
+    };
+    let resp = call_service(&app, login("wrong")).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body: Value = call_and_read_body_json(&app, login("demo123")).await;
+    assert_eq!(body["data"]["user"]["username"], "alice");
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    // 全文检索由迁移创建的触发器维护，fixture 写入的数据同样可以检索
+    let req = TestRequest::get()
+        .uri("/api/users?q=bob")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = call_and_read_body_json(&app, req).await;
+    let names: Vec<&str> = body["data"]["list"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|u| u["username"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["bob"]);
+
+    // 游标翻页时没有页码，page 参数被忽略
+    let list = |query: &str| {
+        TestRequest::get()
+            .uri(&format!("/api/users?sort=id&size=1&{}", query))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let body: Value = call_and_read_body_json(&app, list("page=1")).await;
+    assert_eq!(body["data"]["page"], 1);
+    let cursor = body["data"]["next_cursor"].as_str().unwrap().to_string();
+    let body: Value =
+        call_and_read_body_json(&app, list(&format!("page=5&after={}", cursor))).await;
+    assert_eq!(body["data"]["page"], Value::Null);
+    assert_eq!(body["data"]["list"][0]["username"], "alice");
+
+    let req = TestRequest::get().uri("/api/users").to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_register_duplicate() {
+    let pool = memory_pool();
+    let dao = pool.get().unwrap();
+    dao.load_fixture("users", USERS, Format::Json).unwrap();
+    let state = web::Data::new(AppState {
+        dao: AsyncDao::new(pool),
+    });
+    let app = init_service(
+        App::new()
+            .app_data(state)
+            .route("/api/auth/register", web::post().to(handle_register)),
+    )
+    .await;
+
+    let register = |username: &str, email: &str| {
+        TestRequest::post()
+            .uri("/api/auth/register")
+            .set_json(json!({
+                "username": username,
+                "password": "demo123",
+                "confirm_password": "demo123",
+                "email": email,
+            }))
+            .to_request()
+    };
+    let resp = call_service(&app, register("carol", "carol@example.com")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = call_service(&app, register("alice", "other@example.com")).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // 绕过注册检查直接写入时，由唯一索引拒绝，db_error 同样返回 409
+    let mut user = dao.list_all::<User>().unwrap().pop().unwrap();
+    user.id = 0;
+    user.email = "another@example.com".to_string();
+    let err = dao.add(user).unwrap_err();
+    assert_eq!(
+        err.sql_kind(),
+        Some(&SqlErrorKind::UniqueViolation {
+            table: "users".to_string(),
+            columns: vec!["username".to_string()],
+        })
+    );
+    assert_eq!(db_error(&err).status(), StatusCode::CONFLICT);
+}
@@ -33,7 +33,7 @@ pub fn init_user() -> User {
         username: "demo".to_string(),
         password: hash_password("demo123"),
         email: "".to_string(),
-        created_at: Calendar::now().timestamp() as i32,
+        ..Default::default()
     }
 }
 
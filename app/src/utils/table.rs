@@ -1,37 +1,190 @@
-use lib_json::types::Type;
+use lib_log::log;
 use lib_sql::{
     SqliteConnection,
+    migrate::{Migration, Migrator},
+    pool::Pool,
+    schema::{Schema, SchemaCheckMode},
     sources::Dao,
     traits::CommInterface,
-    utils::{Operator, Search},
+    transfer::{Format, ImportOptions},
+    utils::{Matcher, Operator, Search},
 };
 use crate::{model::User, utils::auth::init_user};
 
-pub fn init() -> Result<(), lib_sql::traits::Error> {
-    let dao = Dao::new()?;
+pub fn init(pool: &Pool, mode: SchemaCheckMode) -> Result<(), lib_sql::traits::Error> {
+    let dao = pool.get()?;
     init_table(&dao)?;
+    check_schema(&dao, mode)?;
     init_data(&dao)?;
     Ok(())
 }
 
+// 检查实体与数据库表结构是否一致，strict 模式下不一致时返回错误
+fn check_schema(dao: &Dao<SqliteConnection>, mode: SchemaCheckMode) -> Result<(), lib_sql::traits::Error> {
+    if mode == SchemaCheckMode::Off {
+        return Ok(());
+    }
+    let report = dao.check_schema(&schema())?;
+    if report.is_ok() {
+        return Ok(());
+    }
+    for issue in &report.issues {
+        log::log_warn(&format!("schema drift: {}", issue));
+    }
+    if mode == SchemaCheckMode::Strict {
+        return Err(lib_sql::traits::Error::SchemaError(report.to_string()));
+    }
+    Ok(())
+}
+
+// 需要检查表结构的实体，新增实体时在这里注册
+fn schema() -> Schema {
+    Schema::new().register::<User>()
+}
 
-// 定义 init_table 函数，用于初始化数据库表
+// 执行迁移命令：migrate status | migrate rollback <version>
+pub fn migrate_command(pool: &Pool, args: &[String]) -> Result<(), lib_sql::traits::Error> {
+    let dao = pool.get()?;
+    let migrator = migrator()?;
+    match args.first().map(|s| s.as_str()) {
+        Some("status") | None => {
+            for m in dao.migration_status(&migrator)? {
+                let state = if m.is_applied() { "applied" } else { "pending" };
+                println!("{:>4} {:<24} {}", m.version, m.name, state);
+            }
+        }
+        Some("rollback") => {
+            let version = args
+                .get(1)
+                .and_then(|v| v.parse().ok())
+                .ok_or(lib_sql::traits::Error::ArgError("usage: migrate rollback <version>".to_string()))?;
+            let versions = dao.rollback_to(&migrator, version)?;
+            log::log_info(&format!("rolled back migrations: {:?}", versions));
+        }
+        Some(cmd) => {
+            return Err(lib_sql::traits::Error::ArgError(format!("unknown migrate command: {}", cmd)));
+        }
+    }
+    Ok(())
+}
+
+// 执行数据库维护命令，服务运行中也可以执行：
+// db backup | db list | db prune <keep> | db restore <file> | db vacuum | db analyze | db check
+// db export <table> <file> | db import <table> <file> [--dry-run]
+pub fn db_command(pool: &Pool, args: &[String]) -> Result<(), lib_sql::traits::Error> {
+    let dao = pool.get()?;
+    match args.first().map(|s| s.as_str()) {
+        Some("backup") => {
+            let path = dao.backup()?;
+            log::log_info(&format!("backup to {}", path.display()));
+        }
+        Some("list") | None => {
+            for path in dao.list_backups()? {
+                println!("{}", path.display());
+            }
+        }
+        Some("prune") => {
+            let keep = args
+                .get(1)
+                .and_then(|v| v.parse().ok())
+                .ok_or(lib_sql::traits::Error::ArgError("usage: db prune <keep>".to_string()))?;
+            for path in dao.prune_backups(keep)? {
+                log::log_info(&format!("removed backup {}", path.display()));
+            }
+        }
+        Some("restore") => {
+            let file = args
+                .get(1)
+                .ok_or(lib_sql::traits::Error::ArgError("usage: db restore <file>".to_string()))?;
+            // 恢复前先备份当前数据，恢复错误时可以找回
+            let path = dao.backup()?;
+            log::log_info(&format!("backup to {}", path.display()));
+            dao.restore_from(file)?;
+            log::log_info(&format!("restored from {}", file));
+        }
+        Some("vacuum") => dao.vacuum()?,
+        Some("analyze") => dao.analyze()?,
+        Some("check") => {
+            let problems = dao.integrity_check()?;
+            if !problems.is_empty() {
+                return Err(lib_sql::traits::Error::SchemaError(problems.join("; ")));
+            }
+            println!("ok");
+        }
+        Some("export") => {
+            let (Some(table), Some(file)) = (args.get(1), args.get(2)) else {
+                return Err(lib_sql::traits::Error::ArgError("usage: db export <table> <file>".to_string()));
+            };
+            let data = match table.as_str() {
+                "users" => dao.export::<User>(&Search::new(), transfer_format(file)?)?,
+                _ => return Err(unknown_table(table)),
+            };
+            std::fs::write(file, data)
+                .map_err(|e| lib_sql::traits::Error::ConfigError(format!("write {} error:{}", file, e)))?;
+            log::log_info(&format!("exported {} to {}", table, file));
+        }
+        Some("import") => {
+            let (Some(table), Some(file)) = (args.get(1), args.get(2)) else {
+                return Err(lib_sql::traits::Error::ArgError(
+                    "usage: db import <table> <file> [--dry-run]".to_string(),
+                ));
+            };
+            let data = std::fs::read_to_string(file)
+                .map_err(|e| lib_sql::traits::Error::ConfigError(format!("read {} error:{}", file, e)))?;
+            let options = ImportOptions::new().dry_run(args.iter().any(|a| a == "--dry-run"));
+            let report = match table.as_str() {
+                "users" => dao.import::<User>(&data, transfer_format(file)?, &options)?,
+                _ => return Err(unknown_table(table)),
+            };
+            for e in &report.errors {
+                log::log_warn(&format!("import {}: {}", table, e));
+            }
+            log::log_info(&format!(
+                "import {}: {} of {} rows ok, committed: {}",
+                table, report.imported, report.total, report.committed
+            ));
+            if !report.is_ok() {
+                return Err(lib_sql::traits::Error::ArgError(format!(
+                    "{} rows of {} can not be imported",
+                    report.errors.len(),
+                    file
+                )));
+            }
+        }
+        Some(cmd) => {
+            return Err(lib_sql::traits::Error::ArgError(format!("unknown db command: {}", cmd)));
+        }
+    }
+    Ok(())
+}
+
+// 按文件的扩展名确定导入导出的格式：.json、.jsonl、.csv
+fn transfer_format(file: &str) -> Result<Format, lib_sql::traits::Error> {
+    Format::from_path(file).ok_or(lib_sql::traits::Error::ArgError(format!(
+        "unsupported file type: {}, expected .json, .jsonl or .csv",
+        file
+    )))
+}
+
+fn unknown_table(table: &str) -> lib_sql::traits::Error {
+    lib_sql::traits::Error::ArgError(format!("unknown table: {}", table))
+}
+
+// 定义 init_table 函数，执行未执行的数据库迁移
 fn init_table(dao: &Dao<SqliteConnection>) -> Result<(), lib_sql::traits::Error> {
-    dao.create_table(&init_table_user())?;
+    let versions = dao.migrate(&migrator()?)?;
+    if !versions.is_empty() {
+        log::log_info(&format!("applied migrations: {:?}", versions));
+    }
     Ok(())
 }
 
 // 定义 init_data 函数，用于初始化默认的用户
 fn init_data(dao: &Dao<SqliteConnection>) -> Result<(), lib_sql::traits::Error> {
     let user = init_user();
-    let mut search = Search::default();
-    search.matcher.and(
-        "username",
-        Operator::Eq,
-        Type::String(user.username.to_string()),
-    );
-    let users = dao.list::<User>(&mut search)?;
-    if users.len() > 0 {
+    let mut matcher = Matcher::new();
+    matcher.and("username", Operator::Eq, &user.username);
+    if dao.exists::<User>(matcher.with_deleted())? {
         return Ok(());
     }
 
@@ -39,15 +192,69 @@ fn init_data(dao: &Dao<SqliteConnection>) -> Result<(), lib_sql::traits::Error>
     Ok(())
 }
 
-
-// 创建用户表
-fn init_table_user() -> String {
-    r#"CREATE TABLE IF NOT EXISTS users (
+// 数据库迁移，已发布的版本不要修改，表结构变更时追加新的版本
+fn migrator() -> Result<Migrator, lib_sql::traits::Error> {
+    Migrator::new(vec![
+        Migration::new(
+            1,
+            "create_users",
+            r#"CREATE TABLE IF NOT EXISTS users (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             username TEXT NOT NULL,
             password TEXT NOT NULL,
             email TEXT NOT NULL,
             created_at INTEGER NOT NULL
-        )"#
-    .to_string()
-}
\ No newline at end of file
+        )"#,
+            "DROP TABLE IF EXISTS users",
+        ),
+        Migration::new(
+            2,
+            "users_soft_delete",
+            "ALTER TABLE users ADD COLUMN deleted_at INTEGER",
+            "ALTER TABLE users DROP COLUMN deleted_at",
+        ),
+        Migration::new(
+            3,
+            "users_updated_at",
+            "ALTER TABLE users ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+            UPDATE users SET updated_at = created_at",
+            "ALTER TABLE users DROP COLUMN updated_at",
+        ),
+        Migration::new(
+            4,
+            "users_version",
+            "ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 0",
+            "ALTER TABLE users DROP COLUMN version",
+        ),
+        // 用户名与邮箱的全文索引，与 lib_sql::fts::create_fts_sql::<User>() 生成的语句一致
+        Migration::new(
+            5,
+            "users_fts",
+            r#"CREATE VIRTUAL TABLE IF NOT EXISTS users_fts USING fts5(username, email, content='users');
+            CREATE TRIGGER IF NOT EXISTS users_fts_ai AFTER INSERT ON users BEGIN
+                INSERT INTO users_fts(rowid, username, email) VALUES (new.rowid, new.username, new.email);
+            END;
+            CREATE TRIGGER IF NOT EXISTS users_fts_ad AFTER DELETE ON users BEGIN
+                INSERT INTO users_fts(users_fts, rowid, username, email) VALUES ('delete', old.rowid, old.username, old.email);
+            END;
+            CREATE TRIGGER IF NOT EXISTS users_fts_au AFTER UPDATE OF username, email ON users BEGIN
+                INSERT INTO users_fts(users_fts, rowid, username, email) VALUES ('delete', old.rowid, old.username, old.email);
+                INSERT INTO users_fts(rowid, username, email) VALUES (new.rowid, new.username, new.email);
+            END;
+            INSERT INTO users_fts(users_fts) VALUES ('rebuild')"#,
+            r#"DROP TRIGGER IF EXISTS users_fts_ai;
+            DROP TRIGGER IF EXISTS users_fts_ad;
+            DROP TRIGGER IF EXISTS users_fts_au;
+            DROP TABLE IF EXISTS users_fts"#,
+        ),
+        // 用户名与邮箱唯一，绕过注册检查的写入（如 db import）由数据库拒绝，冲突时返回 409
+        Migration::new(
+            6,
+            "users_unique",
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_users_username ON users (username);
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email ON users (email)",
+            "DROP INDEX IF EXISTS idx_users_username;
+            DROP INDEX IF EXISTS idx_users_email",
+        ),
+    ])
+}
@@ -224,6 +224,25 @@ impl JsonObject {
     pub fn is_empty(&self) -> bool {
         self.list.len() == 0
     }
+
+    /// 按写入顺序返回所有的 key，重复的 key 只返回一次
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = vec![];
+        for pair in &self.list {
+            if !keys.contains(&pair.first.as_str()) {
+                keys.push(&pair.first);
+            }
+        }
+        keys
+    }
+
+    /// 删除 key 对应的所有值，返回第一个值
+    pub fn remove(&mut self, key: &str) -> Option<Type> {
+        let index = self.list.iter().position(|pair| pair.first == key)?;
+        let value = self.list.remove(index).second;
+        self.list.retain(|pair| pair.first != key);
+        Some(value)
+    }
 }
 
 mod string_from_object {
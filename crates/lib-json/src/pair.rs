@@ -23,13 +23,22 @@ impl Debug for Pair {
 
 mod string_from_pair {
     use super::*;
+    use crate::var::encode_json;
 
     impl Pair {
         pub fn to_json(&self) -> String {
-            format!("\"{}\":{}", self.first, &self.second.to_json())
+            format!(
+                "\"{}\":{}",
+                encode_json(&self.first),
+                &self.second.to_json()
+            )
         }
         pub(crate) fn to_json_line(&self, indent: usize) -> String {
-            format!("\"{}\": {}", self.first, &self.second.to_json_line(indent))
+            format!(
+                "\"{}\": {}",
+                encode_json(&self.first),
+                &self.second.to_json_line(indent)
+            )
         }
     }
 }
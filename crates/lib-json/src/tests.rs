@@ -125,3 +125,15 @@ fn test_object_to_json_line() {
     let json_line = object.to_json_line();
     assert_eq!(json.to_string(), json_line);
 }
+
+#[test]
+fn test_escape_string() {
+    let text = "say \"hi\"\\\n\tend\u{1}";
+    let mut object = JsonObject::new();
+    object.set_str("text", text);
+    let json = object.to_json();
+    assert_eq!(json, r#"{"text":"say \"hi\"\\\n\tend\u0001"}"#);
+
+    let object = parse_object(&json).unwrap();
+    assert_eq!(object.get_str("text"), Some(text));
+}
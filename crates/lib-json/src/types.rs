@@ -1,6 +1,10 @@
 use std::fmt::Display;
 
-use crate::{list::JsonList, object::JsonObject, var::StringExt};
+use crate::{
+    list::JsonList,
+    object::JsonObject,
+    var::{StringExt, encode_json},
+};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Wrap<T>(pub T);
@@ -78,7 +82,7 @@ impl Type {
 impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            Type::String(s) => format!("\"{}\"", s),
+            Type::String(s) => format!("\"{}\"", encode_json(s)),
             Type::I8(i) => i.to_string(),
             Type::I16(i) => i.to_string(),
             Type::I32(i) => i.to_string(),
@@ -164,11 +164,11 @@ pub fn decode_json(js: String) -> Option<String> {
         if in_trans {
             match c {
                 '"' | '\'' | '/' | '\\' => buf.push_str(c.to_string().as_str()),
-                'b' => buf.push_str(r"\b"),
-                'f' => buf.push_str(r"\f"),
-                'n' => buf.push_str(r"\n"),
-                'r' => buf.push_str(r"\r"),
-                't' => buf.push_str(r"\t"),
+                'b' => buf.push('\u{8}'),
+                'f' => buf.push('\u{c}'),
+                'n' => buf.push('\n'),
+                'r' => buf.push('\r'),
+                't' => buf.push('\t'),
                 'u' => {
                     // unicode 解码
                     let start_index = (i + 1) as isize;
@@ -195,6 +195,25 @@ pub fn decode_json(js: String) -> Option<String> {
     return Some(buf);
 }
 
+/// 将字符串编码为 json 字符串的内容（不含两端的引号），转义引号、反斜杠及控制字符，与 decode_json 互逆
+pub fn encode_json(s: &str) -> String {
+    let mut buf = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            '\u{8}' => buf.push_str("\\b"),
+            '\u{c}' => buf.push_str("\\f"),
+            c if (c as u32) < 0x20 => buf.push_str(&format!("\\u{:04x}", c as u32)),
+            c => buf.push(c),
+        }
+    }
+    buf
+}
+
 // unicode to char
 fn unicode_to_char(unicode: &str) -> Option<char> {
     // 去除前面的转义字符，提取出Unicode码点
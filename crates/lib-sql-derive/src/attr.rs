@@ -0,0 +1,150 @@
+use syn::{Attribute, LitStr, Result};
+
+/// 结构体上的 `#[table(...)]` 属性
+#[derive(Default)]
+pub struct TableAttr {
+    /// 表名，默认为结构体名的蛇形命名
+    pub name: Option<String>,
+    /// 使用 Hooks 中实现的生命周期钩子
+    pub hooks: bool,
+}
+
+impl TableAttr {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut table = TableAttr::default();
+        for attr in attrs {
+            if !attr.path().is_ident("table") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    table.name = Some(value.value());
+                    return Ok(());
+                }
+                if meta.path.is_ident("hooks") {
+                    table.hooks = true;
+                    return Ok(());
+                }
+                Err(meta.error("unsupported table attribute"))
+            })?;
+        }
+        Ok(table)
+    }
+}
+
+/// 字段上的 `#[id(...)]` 与 `#[column(...)]` 属性
+#[derive(Default)]
+pub struct FieldAttr {
+    /// 是否为主键
+    pub id: bool,
+    /// 主键是否自增
+    pub auto_increment: bool,
+    /// 字段对应的列名，默认为字段名
+    pub rename: Option<String>,
+    /// 不映射到表字段，读取时使用 Default
+    pub skip: bool,
+    /// 唯一约束
+    pub unique: bool,
+    /// 创建普通索引
+    pub index: bool,
+    /// 默认值的 sql 表达式
+    pub default: Option<String>,
+    /// 字段的 sql 类型，默认根据字段类型推断
+    pub sql_type: Option<LitStr>,
+    /// 允许客户端按该字段过滤
+    pub filterable: bool,
+    /// 允许客户端按该字段排序
+    pub sortable: bool,
+    /// 参与全文检索
+    pub searchable: bool,
+    /// 软删除字段，保存删除时间
+    pub soft_delete: bool,
+    /// 创建时间字段
+    pub created_at: bool,
+    /// 修改时间字段
+    pub updated_at: bool,
+    /// 乐观锁的版本号字段
+    pub version: bool,
+}
+
+impl FieldAttr {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut field = FieldAttr::default();
+        for attr in attrs {
+            if attr.path().is_ident("id") {
+                field.id = true;
+                // 允许只写 #[id]
+                if matches!(attr.meta, syn::Meta::Path(_)) {
+                    continue;
+                }
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("auto_increment") {
+                        field.auto_increment = true;
+                        return Ok(());
+                    }
+                    Err(meta.error("unsupported id attribute"))
+                })?;
+            } else if attr.path().is_ident("column") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        let value: LitStr = meta.value()?.parse()?;
+                        field.rename = Some(value.value());
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("skip") {
+                        field.skip = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("unique") {
+                        field.unique = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("index") {
+                        field.index = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("default") {
+                        let value: LitStr = meta.value()?.parse()?;
+                        field.default = Some(value.value());
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("filterable") {
+                        field.filterable = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("sortable") {
+                        field.sortable = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("searchable") {
+                        field.searchable = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("created_at") {
+                        field.created_at = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("updated_at") {
+                        field.updated_at = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("version") {
+                        field.version = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("soft_delete") {
+                        field.soft_delete = true;
+                        return Ok(());
+                    }
+                    if meta.path.is_ident("sql_type") {
+                        field.sql_type = Some(meta.value()?.parse()?);
+                        return Ok(());
+                    }
+                    Err(meta.error("unsupported column attribute"))
+                })?;
+            }
+        }
+        Ok(field)
+    }
+}
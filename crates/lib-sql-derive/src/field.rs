@@ -0,0 +1,155 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use syn::{GenericArgument, PathArguments, Type};
+
+/// 字段支持的基础类型
+pub enum Kind {
+    Int(Ident),
+    Uint(Ident),
+    Float(Ident),
+    Bool,
+    String,
+}
+
+/// 字段类型，Option<T> 的字段允许为 NULL
+pub struct FieldType {
+    pub kind: Kind,
+    pub optional: bool,
+}
+
+impl FieldType {
+    /// 解析字段类型，不支持的类型返回 None
+    pub fn parse(ty: &Type) -> Option<Self> {
+        if let Some(inner) = option_inner(ty) {
+            return Some(FieldType {
+                kind: parse_kind(inner)?,
+                optional: true,
+            });
+        }
+        Some(FieldType {
+            kind: parse_kind(ty)?,
+            optional: false,
+        })
+    }
+
+    /// 从 JsonObject 取值的表达式，结果为 Option<T>
+    pub fn getter(&self, column: &str) -> TokenStream {
+        match &self.kind {
+            Kind::Int(t) => {
+                let get = format_ident!("get_{}", t);
+                quote! { row.#get(#column) }
+            }
+            Kind::Uint(t) => {
+                // sqlite 读出来的整数均为 i64
+                let get = format_ident!("get_{}", t);
+                quote! {
+                    row.#get(#column)
+                        .or_else(|| row.get_i64(#column).and_then(|v| #t::try_from(v).ok()))
+                }
+            }
+            Kind::Float(t) => {
+                let get = format_ident!("get_{}", t);
+                quote! { row.#get(#column).or_else(|| row.get_i64(#column).map(|v| v as #t)) }
+            }
+            Kind::Bool => {
+                // sqlite 没有布尔类型，以 0/1 保存
+                quote! { row.get_bool(#column).or_else(|| row.get_i64(#column).map(|v| v != 0)) }
+            }
+            Kind::String => quote! { row.get_str(#column).map(|v| v.to_string()) },
+        }
+    }
+
+    /// 字段的 rust 类型名，用于读取失败时的错误信息
+    pub fn type_name(&self) -> String {
+        match &self.kind {
+            Kind::Int(t) | Kind::Uint(t) | Kind::Float(t) => t.to_string(),
+            Kind::Bool => "bool".to_string(),
+            Kind::String => "String".to_string(),
+        }
+    }
+
+    /// 字段类型对应的 sql 类型名
+    pub fn sql_type(&self) -> &'static str {
+        match &self.kind {
+            Kind::Int(_) | Kind::Uint(_) | Kind::Bool => "Integer",
+            Kind::Float(_) => "Real",
+            Kind::String => "Text",
+        }
+    }
+
+    /// 将引用 `v` 写入 JsonObject 的语句
+    pub fn setter(&self, column: &str) -> TokenStream {
+        match &self.kind {
+            Kind::Int(t) | Kind::Uint(t) | Kind::Float(t) => {
+                let set = format_ident!("set_{}", t);
+                quote! { obj.#set(#column, *v); }
+            }
+            Kind::Bool => quote! { obj.set_bool(#column, *v); },
+            Kind::String => quote! { obj.set_str(#column, v); },
+        }
+    }
+}
+
+fn parse_kind(ty: &Type) -> Option<Kind> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let ident = &path.path.segments.last()?.ident;
+    let name = ident.to_string();
+    let kind = match name.as_str() {
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => Kind::Int(ident.clone()),
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => Kind::Uint(ident.clone()),
+        "f32" | "f64" => Kind::Float(ident.clone()),
+        "bool" => Kind::Bool,
+        "String" => Kind::String,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+// 取出 Option<T> 中的 T
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+/// 将 `#[column(sql_type = "...")]` 转为 SqlType 的变体名
+pub fn parse_sql_type(name: &str) -> Option<&'static str> {
+    match name.trim().to_uppercase().as_str() {
+        "INTEGER" => Some("Integer"),
+        "REAL" => Some("Real"),
+        "TEXT" => Some("Text"),
+        "BLOB" => Some("Blob"),
+        "NUMERIC" => Some("Numeric"),
+        _ => None,
+    }
+}
+
+/// 结构体名转为蛇形命名的表名，如 UserRole -> user_role
+pub fn snake_case(ident: &Ident) -> String {
+    let mut name = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
@@ -0,0 +1,367 @@
+//! # lib-sql-derive
+//!
+//! 为 lib-sql 的实体结构体派生 `Table` trait，如：
+//! ```ignore
+//! use lib_sql::traits::Table;
+//!
+//! #[derive(Debug, Default, Table)]
+//! #[table(name = "users", hooks)]
+//! struct User {
+//!     #[id(auto_increment)]
+//!     id: i32,
+//!     #[column(searchable)]
+//!     username: String,
+//!     #[column(rename = "mail", filterable, sortable)]
+//!     email: Option<String>,
+//!     #[column(skip)]
+//!     token: String,
+//!     #[column(created_at)]
+//!     created_at: i64,
+//!     #[column(updated_at)]
+//!     updated_at: i64,
+//!     #[column(version)]
+//!     version: i64,
+//!     #[column(soft_delete)]
+//!     deleted_at: Option<i64>,
+//! }
+//!
+//! // 使用 hooks 时需要实现 Hooks，未实现的钩子不做处理
+//! impl lib_sql::traits::Hooks for User {}
+//! ```
+
+mod attr;
+mod field;
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Error, Fields, parse_macro_input, spanned::Spanned};
+
+use crate::{
+    attr::{FieldAttr, TableAttr},
+    field::{FieldType, Kind, parse_sql_type, snake_case},
+};
+
+#[proc_macro_derive(Table, attributes(table, id, column))]
+pub fn derive_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+    let table = TableAttr::parse(&input.attrs)?;
+    let table_name = table.name.unwrap_or_else(|| snake_case(ident));
+
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(input.span(), "Table can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new(input.span(), "Table requires named fields"));
+    };
+
+    let mut parsed = vec![];
+    for f in &fields.named {
+        parsed.push((f, FieldAttr::parse(&f.attrs)?));
+    }
+
+    // 未标记 #[id] 时，使用名为 id 的字段作为主键
+    let mut ids = parsed.iter().filter(|(_, attr)| attr.id && !attr.skip);
+    let id = match (ids.next(), ids.next()) {
+        (Some(_), Some((f, _))) => return Err(Error::new(f.span(), "duplicate #[id] field")),
+        (Some(id), None) => id,
+        (None, _) => match parsed.iter().find(|(f, _)| f.ident.as_ref().unwrap() == "id") {
+            Some(id) => id,
+            None => {
+                return Err(Error::new(
+                    input.span(),
+                    "Table requires an #[id] field or a field named `id`",
+                ));
+            }
+        },
+    };
+    let id_field = id.0.ident.clone().unwrap();
+    let id_column = id.1.rename.clone().unwrap_or_else(|| id_field.to_string());
+    let id_auto = id.1.auto_increment;
+
+    let mut columns = vec![];
+    let mut filterable = vec![];
+    let mut sortable = vec![];
+    let mut searchable = vec![];
+    let mut soft_delete = None;
+    let mut created_at = None;
+    let mut updated_at = None;
+    let mut version = None;
+    let mut defs = vec![];
+    let mut loads = vec![];
+    let mut saves = vec![];
+    for (f, attr) in &parsed {
+        let name = f.ident.as_ref().unwrap();
+        if attr.skip {
+            loads.push(quote! { #name: ::core::default::Default::default() });
+            continue;
+        }
+        let column = attr.rename.clone().unwrap_or_else(|| name.to_string());
+        let Some(ty) = FieldType::parse(&f.ty) else {
+            return Err(Error::new(
+                f.ty.span(),
+                "unsupported field type, expected integer, float, bool, String or Option<T>",
+            ));
+        };
+        defs.push(column_def(&column, &ty, attr, *name == id_field)?);
+
+        let getter = ty.getter(&column);
+        let setter = ty.setter(&column);
+        let expected = ty.type_name();
+        let decode = quote! {
+            || ::lib_sql::traits::Error::Decode {
+                column: #column.to_string(),
+                expected: #expected,
+            }
+        };
+        if ty.optional {
+            loads.push(quote! {
+                #name: match row.get_data(#column) {
+                    None | Some(::lib_json::types::Type::Null) => None,
+                    Some(_) => Some(#getter.ok_or_else(#decode)?),
+                }
+            });
+            saves.push(quote! {
+                match &self.#name {
+                    Some(v) => { #setter }
+                    None => { obj.set_null(#column); }
+                }
+            });
+        } else {
+            loads.push(quote! {
+                #name: #getter.ok_or_else(#decode)?
+            });
+            saves.push(quote! {
+                {
+                    let v = &self.#name;
+                    #setter
+                }
+            });
+        }
+        if attr.filterable {
+            filterable.push(column.clone());
+        }
+        if attr.sortable {
+            sortable.push(column.clone());
+        }
+        if attr.searchable {
+            if !matches!(ty.kind, Kind::String) {
+                return Err(Error::new(
+                    f.ty.span(),
+                    "searchable column must be a String or Option<String>",
+                ));
+            }
+            searchable.push(column.clone());
+        }
+        if attr.soft_delete {
+            if !ty.optional || !matches!(ty.kind, Kind::Int(_)) {
+                return Err(Error::new(
+                    f.ty.span(),
+                    "soft_delete column must be an optional integer, e.g. Option<i64>",
+                ));
+            }
+            if soft_delete.is_some() {
+                return Err(Error::new(f.span(), "duplicate soft_delete column"));
+            }
+            soft_delete = Some(column.clone());
+        }
+        if attr.version {
+            if ty.optional || !matches!(ty.kind, Kind::Int(_)) {
+                return Err(Error::new(
+                    f.ty.span(),
+                    "version column must be an integer, e.g. i64",
+                ));
+            }
+            if version.is_some() {
+                return Err(Error::new(f.span(), "duplicate version column"));
+            }
+            version = Some(column.clone());
+        }
+        for (flag, slot, name) in [
+            (attr.created_at, &mut created_at, "created_at"),
+            (attr.updated_at, &mut updated_at, "updated_at"),
+        ] {
+            if !flag {
+                continue;
+            }
+            if !matches!(ty.kind, Kind::Int(_)) {
+                return Err(Error::new(
+                    f.ty.span(),
+                    format!("{} column must be an integer timestamp", name),
+                ));
+            }
+            if slot.is_some() {
+                return Err(Error::new(f.span(), format!("duplicate {} column", name)));
+            }
+            *slot = Some(column.clone());
+        }
+        columns.push(column);
+    }
+
+    let soft_delete = soft_delete.map(|column| {
+        quote! {
+            fn soft_delete_column() -> Option<&'static str> {
+                Some(#column)
+            }
+        }
+    });
+    let created_at = created_at.map(|column| {
+        quote! {
+            fn created_at_column() -> Option<&'static str> {
+                Some(#column)
+            }
+        }
+    });
+    let updated_at = updated_at.map(|column| {
+        quote! {
+            fn updated_at_column() -> Option<&'static str> {
+                Some(#column)
+            }
+        }
+    });
+    let version = version.map(|column| {
+        quote! {
+            fn version_column() -> Option<&'static str> {
+                Some(#column)
+            }
+        }
+    });
+    // 转发到 Hooks 中的实现
+    let hooks = table.hooks.then(|| {
+        quote! {
+            fn before_insert(&mut self) -> Result<(), ::lib_sql::traits::Error> {
+                ::lib_sql::traits::Hooks::before_insert(self)
+            }
+
+            fn before_update(&mut self) -> Result<(), ::lib_sql::traits::Error> {
+                ::lib_sql::traits::Hooks::before_update(self)
+            }
+
+            fn after_load(&mut self) -> Result<(), ::lib_sql::traits::Error> {
+                ::lib_sql::traits::Hooks::after_load(self)
+            }
+
+            fn before_delete(&self) -> Result<(), ::lib_sql::traits::Error> {
+                ::lib_sql::traits::Hooks::before_delete(self)
+            }
+        }
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lib_sql::traits::Table for #ident #ty_generics #where_clause {
+            fn id() -> &'static str {
+                #id_column
+            }
+
+            fn id_auto_increase() -> bool {
+                #id_auto
+            }
+
+            fn columns() -> Vec<&'static str> {
+                vec![#(#columns),*]
+            }
+
+            fn column_defs() -> Vec<::lib_sql::traits::Column> {
+                vec![#(#defs),*]
+            }
+
+            fn filterable() -> Vec<&'static str> {
+                vec![#(#filterable),*]
+            }
+
+            fn sortable() -> Vec<&'static str> {
+                vec![#(#sortable),*]
+            }
+
+            fn searchable() -> Vec<&'static str> {
+                vec![#(#searchable),*]
+            }
+
+            #soft_delete
+
+            #created_at
+
+            #updated_at
+
+            #version
+
+            #hooks
+
+            fn id_value(&self) -> String {
+                self.#id_field.to_string()
+            }
+
+            fn table_name() -> &'static str {
+                #table_name
+            }
+
+            fn from_json_object(
+                row: &::lib_json::object::JsonObject,
+            ) -> Result<Self, ::lib_sql::traits::Error> {
+                Ok(Self {
+                    #(#loads),*
+                })
+            }
+
+            fn to_json_object(
+                &self,
+            ) -> Result<::lib_json::object::JsonObject, ::lib_sql::traits::Error> {
+                let mut obj = ::lib_json::object::JsonObject::new();
+                #(#saves)*
+                Ok(obj)
+            }
+        }
+    })
+}
+
+// 生成字段描述 Column 的表达式
+fn column_def(
+    column: &str,
+    ty: &FieldType,
+    attr: &FieldAttr,
+    is_id: bool,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let sql_type = match &attr.sql_type {
+        Some(lit) => match parse_sql_type(&lit.value()) {
+            Some(t) => t,
+            None => {
+                return Err(Error::new(
+                    lit.span(),
+                    "unsupported sql_type, expected INTEGER, REAL, TEXT, BLOB or NUMERIC",
+                ));
+            }
+        },
+        None => ty.sql_type(),
+    };
+    let sql_type = format_ident!("{}", sql_type);
+    let mut def = quote! {
+        ::lib_sql::traits::Column::new(#column, ::lib_sql::traits::SqlType::#sql_type)
+    };
+    if ty.optional {
+        def = quote! { #def.nullable() };
+    }
+    if is_id {
+        def = quote! { #def.primary_key() };
+        if attr.auto_increment {
+            def = quote! { #def.auto_increment() };
+        }
+    }
+    if attr.unique {
+        def = quote! { #def.unique() };
+    }
+    if attr.index {
+        def = quote! { #def.index() };
+    }
+    if let Some(default) = &attr.default {
+        def = quote! { #def.default(#default) };
+    }
+    Ok(def)
+}
@@ -0,0 +1,103 @@
+use crate::utils::{Comparator, Matcher, Search};
+
+/// 统计查询的构造器，按分组字段计算 count、sum、avg、min、max，生成 aggregate 使用的 Search
+/// # Examples
+/// ```
+/// use lib_sql::aggregate::Aggregate;
+/// use lib_sql::utils::{Matcher, Operator};
+///
+/// let mut matcher = Matcher::new();
+/// matcher.and("age", Operator::Gt, 1);
+/// let search = Aggregate::new()
+///     .group_by("name")
+///     .count("cnt")
+///     .sum("age", "total_age")
+///     .avg("age", "avg_age")
+///     .filter(matcher)
+///     .order_by("cnt", true)
+///     .to_search();
+/// assert_eq!(
+///     search.parse("animals").sql,
+///     "select name,count(*) as cnt,sum(age) as total_age,avg(age) as avg_age from animals \
+///      where ((age > ?)) group by name order by cnt desc"
+/// );
+/// // let rows = dao.aggregate::<Animal>(&search)?;
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Aggregate {
+    groups: Vec<String>,
+    fields: Vec<String>,
+    matcher: Matcher,
+    sort: Comparator,
+}
+
+impl Aggregate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加分组字段，分组字段同时出现在结果中
+    pub fn group_by(mut self, column: &str) -> Self {
+        self.groups.push(column.to_string());
+        self
+    }
+
+    /// 每组的行数
+    pub fn count(self, alias: &str) -> Self {
+        self.field("count", "*", alias)
+    }
+
+    /// 每组 column 的和，组内全为 null 时结果为 null
+    pub fn sum(self, column: &str, alias: &str) -> Self {
+        self.field("sum", column, alias)
+    }
+
+    /// 每组 column 的平均值，结果为浮点数
+    pub fn avg(self, column: &str, alias: &str) -> Self {
+        self.field("avg", column, alias)
+    }
+
+    pub fn min(self, column: &str, alias: &str) -> Self {
+        self.field("min", column, alias)
+    }
+
+    pub fn max(self, column: &str, alias: &str) -> Self {
+        self.field("max", column, alias)
+    }
+
+    /// 统计前的过滤条件
+    pub fn filter(mut self, matcher: Matcher) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// 结果的排序，可以使用分组字段或统计字段的别名
+    pub fn order_by(mut self, key: &str, desc: bool) -> Self {
+        self.sort.add(key, desc);
+        self
+    }
+
+    /// 生成查询条件，未添加统计字段时只查询分组字段
+    pub fn to_search(&self) -> Search {
+        let mut search = Search::new();
+        let fileds: Vec<&str> = self
+            .groups
+            .iter()
+            .chain(&self.fields)
+            .map(|f| f.as_str())
+            .collect();
+        if !fileds.is_empty() {
+            search.fileds = fileds.join(",");
+        }
+        search.group = self.groups.join(",");
+        search.matcher = self.matcher.clone();
+        search.sort = self.sort.clone();
+        search
+    }
+
+    fn field(mut self, func: &str, column: &str, alias: &str) -> Self {
+        self.fields
+            .push(format!("{}({}) as {}", func, column, alias));
+        self
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver},
+    },
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use lib_json::object::JsonObject;
+use sqlite::Connection;
+
+use crate::{
+    fts::{TextHit, TextSearch},
+    pool::Pool,
+    relation::Relation,
+    sources::Dao,
+    traits::{CommInterface, Error, Table, TransactionMode},
+    transfer::{Format, ImportOptions, ImportReport},
+    utils::{Matcher, Search, ToType},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// 异步的 Dao，数据库操作在专用的线程中执行，调用方通过 await 等待结果，
+/// 不会阻塞 actix 的工作线程。线程数与连接池的 max_size 一致，克隆得到的实例共享同一组线程
+/// # Examples
+/// ```no_run
+/// use lib_sql::async_dao::AsyncDao;
+/// use lib_sql::pool::Pool;
+///
+/// # async fn demo() -> Result<(), lib_sql::traits::Error> {
+/// let dao = AsyncDao::new(Pool::new_with_path("./conf/config.toml")?);
+/// let version = dao.run(|dao| dao.schema_version()).await?;
+/// // let user = dao.get::<User, _>(1).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncDao {
+    pool: Pool,
+    jobs: mpsc::Sender<Job>,
+}
+
+impl AsyncDao {
+    /// 创建异步 Dao，并启动 pool.max_size() 个工作线程。所有实例 drop 后工作线程退出
+    pub fn new(pool: Pool) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..pool.max_size() {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("lib-sql-{}", i))
+                .spawn(move || work(receiver))
+                .expect("spawn lib-sql worker");
+        }
+        Self { pool, jobs }
+    }
+
+    /// 使用的连接池
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// 在工作线程中取一个连接执行 f，返回 f 的结果
+    pub fn run<R, F>(&self, f: F) -> DaoFuture<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Dao<Connection>) -> Result<R, Error> + Send + 'static,
+    {
+        let shared = Arc::new(Mutex::new(Shared {
+            done: false,
+            result: None,
+            waker: None,
+        }));
+        let sender = Sender {
+            shared: shared.clone(),
+        };
+        let pool = self.pool.clone();
+        let job: Job = Box::new(move || {
+            let result = pool.get().and_then(|dao| f(&dao));
+            sender.send(result);
+        });
+        // 工作线程已全部退出时 job 随错误一起被 drop，由 Sender 写入错误
+        let _ = self.jobs.send(job);
+        DaoFuture { shared }
+    }
+
+    /// 在事务中执行 f，见 CommInterface::transaction
+    pub fn transaction<R, F>(&self, f: F) -> DaoFuture<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Dao<Connection>) -> Result<R, Error> + Send + 'static,
+    {
+        self.run(|dao| dao.transaction(f))
+    }
+
+    /// 在事务中执行 f，可指定加锁方式，见 CommInterface::transaction_with
+    pub fn transaction_with<R, F>(&self, mode: TransactionMode, f: F) -> DaoFuture<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Dao<Connection>) -> Result<R, Error> + Send + 'static,
+    {
+        self.run(move |dao| dao.transaction_with(mode, f))
+    }
+
+    /// 按条件查询，返回列表与更新了 total 的 search
+    pub fn list<T: Table + Send + 'static>(
+        &self,
+        mut search: Search,
+    ) -> DaoFuture<(Vec<T>, Search)> {
+        self.run(move |dao| {
+            let list = dao.list::<T>(&mut search)?;
+            Ok((list, search))
+        })
+    }
+
+    /// 统计查询，见 CommInterface::aggregate
+    pub fn aggregate<T: Table + Send + 'static>(
+        &self,
+        search: Search,
+    ) -> DaoFuture<Vec<JsonObject>> {
+        self.run(move |dao| dao.aggregate::<T>(&search))
+    }
+
+    /// 全文检索，见 Dao::search_text
+    pub fn search_text<T: Table + Send + 'static>(
+        &self,
+        search: TextSearch,
+    ) -> DaoFuture<Vec<TextHit<T>>> {
+        self.run(move |dao| dao.search_text::<T>(&search))
+    }
+
+    /// 导出为 JSON、JSON Lines 或 CSV，见 Dao::export
+    pub fn export<T: Table + Send + 'static>(
+        &self,
+        search: Search,
+        format: Format,
+    ) -> DaoFuture<String> {
+        self.run(move |dao| dao.export::<T>(&search, format))
+    }
+
+    /// 导入 JSON、JSON Lines 或 CSV，见 Dao::import
+    pub fn import<T: Table + Send + 'static>(
+        &self,
+        data: String,
+        format: Format,
+        options: ImportOptions,
+    ) -> DaoFuture<ImportReport> {
+        self.run(move |dao| dao.import::<T>(&data, format, &options))
+    }
+
+    /// 批量读取关联的数据，结果与 parents 一起按顺序返回，见 CommInterface::load_related
+    pub fn load_related<P, C>(
+        &self,
+        parents: Vec<P>,
+        relation: Relation,
+    ) -> DaoFuture<Vec<(P, Vec<C>)>>
+    where
+        P: Table + Send + 'static,
+        C: Table + Send + 'static,
+    {
+        self.run(move |dao| {
+            let related = dao.load_related::<P, C>(&parents, &relation)?;
+            Ok(parents.into_iter().zip(related).collect())
+        })
+    }
+
+    pub fn list_all<T: Table + Send + 'static>(&self) -> DaoFuture<Vec<T>> {
+        self.run(|dao| dao.list_all::<T>())
+    }
+
+    pub fn get<T, I>(&self, id: I) -> DaoFuture<Option<T>>
+    where
+        T: Table + Send + 'static,
+        I: ToType + Send + 'static,
+    {
+        self.run(move |dao| dao.get::<T, I>(id))
+    }
+
+    pub fn find_one<T: Table + Send + 'static>(&self, matcher: Matcher) -> DaoFuture<Option<T>> {
+        self.run(move |dao| dao.find_one::<T>(&matcher))
+    }
+
+    pub fn exists<T: Table + Send + 'static>(&self, matcher: Matcher) -> DaoFuture<bool> {
+        self.run(move |dao| dao.exists::<T>(&matcher))
+    }
+
+    pub fn count<T: Table + Send + 'static>(&self, matcher: Matcher) -> DaoFuture<i64> {
+        self.run(move |dao| dao.count::<T>(&matcher))
+    }
+
+    pub fn add<T: Table + Send + 'static>(&self, entity: T) -> DaoFuture<usize> {
+        self.run(move |dao| dao.add(entity))
+    }
+
+    pub fn insert<T: Table + Send + 'static>(&self, entity: T) -> DaoFuture<i64> {
+        self.run(move |dao| dao.insert(entity))
+    }
+
+    pub fn insert_and_reload<T: Table + Send + 'static>(&self, entity: T) -> DaoFuture<T> {
+        self.run(move |dao| dao.insert_and_reload(entity))
+    }
+
+    pub fn set<T: Table + Send + 'static>(&self, entity: T) -> DaoFuture<usize> {
+        self.run(move |dao| dao.set(entity))
+    }
+
+    pub fn update_where<T: Table + Send + 'static>(
+        &self,
+        matcher: Matcher,
+        values: JsonObject,
+    ) -> DaoFuture<usize> {
+        self.run(move |dao| dao.update_where::<T>(&matcher, values))
+    }
+
+    pub fn delete<T: Table + Send + 'static>(&self, entity: T) -> DaoFuture<usize> {
+        self.run(move |dao| dao.delete(entity))
+    }
+
+    pub fn delete_where<T: Table + Send + 'static>(&self, matcher: Matcher) -> DaoFuture<usize> {
+        self.run(move |dao| dao.delete_where::<T>(&matcher))
+    }
+
+    pub fn restore<T, I>(&self, id: I) -> DaoFuture<usize>
+    where
+        T: Table + Send + 'static,
+        I: ToType + Send + 'static,
+    {
+        self.run(move |dao| dao.restore::<T, I>(id))
+    }
+
+    pub fn purge<T, I>(&self, id: I) -> DaoFuture<usize>
+    where
+        T: Table + Send + 'static,
+        I: ToType + Send + 'static,
+    {
+        self.run(move |dao| dao.purge::<T, I>(id))
+    }
+}
+
+// 工作线程，依次取出任务执行，任务 panic 时不影响后续任务
+fn work(receiver: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        match job {
+            Ok(job) => {
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+struct Shared<R> {
+    // 是否已写入结果，结果被取走后仍为 true
+    done: bool,
+    result: Option<Result<R, Error>>,
+    waker: Option<Waker>,
+}
+
+// 任务的结果发送端，未发送结果就被 drop 时（任务 panic 或工作线程已退出）写入错误
+struct Sender<R> {
+    shared: Arc<Mutex<Shared<R>>>,
+}
+
+impl<R> Sender<R> {
+    fn send(self, result: Result<R, Error>) {
+        self.complete(result);
+    }
+
+    fn complete(&self, result: Result<R, Error>) {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        if shared.done {
+            return;
+        }
+        shared.done = true;
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<R> Drop for Sender<R> {
+    fn drop(&mut self) {
+        self.complete(Err(Error::PoolError(
+            "database task was aborted".to_string(),
+        )));
+    }
+}
+
+/// 数据库操作的结果，await 得到 Result<R, Error>
+pub struct DaoFuture<R> {
+    shared: Arc<Mutex<Shared<R>>>,
+}
+
+impl<R> Future for DaoFuture<R> {
+    type Output = Result<R, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    ffi::{CStr, c_int},
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use lib_date::Calendar;
+use serde::Deserialize;
+use sqlite::Connection;
+use sqlite3_sys as ffi;
+
+use crate::{
+    sources::Dao,
+    traits::{Error, SqlError},
+    utils::{Config, Query},
+};
+
+/// 默认保留的备份个数
+pub const DEFAULT_BACKUP_KEEP: usize = 7;
+
+// 每步复制的页数，步骤之间会释放锁，不长时间阻塞其它连接的写入
+const PAGES_PER_STEP: c_int = 256;
+// 数据库忙时每次等待的时间及最多等待的次数
+const BUSY_WAIT: Duration = Duration::from_millis(10);
+const BUSY_RETRIES: usize = 500;
+
+/// 备份配置，对应配置文件中的 [backup]
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct BackupConfig {
+    /// 备份文件的目录，默认为数据库文件所在目录下的 backup
+    pub dir: Option<String>,
+    /// 保留最近的备份个数，默认 7
+    pub keep: Option<usize>,
+}
+
+// 使用 sqlite 的在线备份接口，将 src 的 main 数据库复制到 dst 的 main 数据库，
+// 复制期间 src 被其它连接修改时 sqlite 会自动重新开始
+fn copy_database(src: &Connection, dst: &Connection) -> Result<(), Error> {
+    let main = c"main";
+    // SAFETY: 两个连接在复制期间均有效，backup 在返回前通过 sqlite3_backup_finish 释放
+    unsafe {
+        let backup =
+            ffi::sqlite3_backup_init(dst.as_raw(), main.as_ptr(), src.as_raw(), main.as_ptr());
+        if backup.is_null() {
+            return Err(last_error(dst, ffi::sqlite3_errcode(dst.as_raw())));
+        }
+        let mut retries = 0;
+        loop {
+            match ffi::sqlite3_backup_step(backup, PAGES_PER_STEP) {
+                ffi::SQLITE_OK => {}
+                ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED if retries < BUSY_RETRIES => {
+                    retries += 1;
+                    thread::sleep(BUSY_WAIT);
+                }
+                // 完成或出错，出错时由 sqlite3_backup_finish 返回错误码
+                _ => break,
+            }
+        }
+        match ffi::sqlite3_backup_finish(backup) {
+            ffi::SQLITE_OK => Ok(()),
+            code => Err(last_error(dst, code)),
+        }
+    }
+}
+
+// 连接上最近一次的错误
+fn last_error(connect: &Connection, code: c_int) -> Error {
+    // SAFETY: sqlite3_errmsg 返回的字符串由 sqlite 管理，在下一次调用接口前有效
+    let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(connect.as_raw())) };
+    SqlError::from_sqlite(&sqlite::Error {
+        code: Some(code as isize),
+        message: Some(message.to_string_lossy().into_owned()),
+    })
+    .into()
+}
+
+// 解析备份文件名中前缀之后的部分，如 20260101-120000 或 20260101-120000-10，
+// 返回日期、时间与序号用于按数值排序，不是 backup 生成的文件返回 None
+fn backup_key(name: &str) -> Option<(u64, u64, u64)> {
+    let number = |s: &str, len: Option<usize>| {
+        let valid = !s.is_empty()
+            && s.chars().all(|c| c.is_ascii_digit())
+            && len.is_none_or(|len| s.len() == len);
+        if valid { s.parse().ok() } else { None }
+    };
+    let mut parts = name.split('-');
+    let date = number(parts.next()?, Some(8))?;
+    let time = number(parts.next()?, Some(6))?;
+    let n = match parts.next() {
+        Some(n) => number(n, None)?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((date, time, n))
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> Error {
+    Error::ConfigError(format!("{} {} error:{}", action, path.display(), e))
+}
+
+// 为 sqlite 实现备份与维护相关的接口
+impl Dao<Connection> {
+    /// 备份文件的目录，默认为数据库文件所在目录下的 backup
+    pub fn backup_dir(&self) -> PathBuf {
+        let backup = self.config.backup.clone().unwrap_or_default();
+        match backup.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(&self.config.datasource)
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join("backup"),
+        }
+    }
+
+    /// 使用 sqlite 的在线备份接口将当前数据库复制到 path，服务运行中也可以执行，期间其它连接可以继续读写。
+    /// 先写入 path.tmp，完成后再重命名，不会留下不完整的备份文件。path 已存在时覆盖
+    pub fn backup_to(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| io_error("create dir", dir, e))?;
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let result = sqlite::open(&tmp)
+            .map_err(|e| SqlError::from_sqlite(&e).into())
+            .and_then(|dst| copy_database(&self.connect, &dst));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        fs::rename(&tmp, path).map_err(|e| io_error("rename", &tmp, e))
+    }
+
+    /// 按 [backup] 配置将数据库备份到带时间的文件，如 ./data/backup/data-20260101-120000.db，
+    /// 然后删除超出保留个数的旧备份，返回备份文件的路径
+    pub fn backup(&self) -> Result<PathBuf, Error> {
+        let datasource = Path::new(&self.config.datasource);
+        let stem = datasource
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "data".to_string());
+        let ext = datasource
+            .extension()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "db".to_string());
+
+        // 同一秒内多次备份时加上序号，按文件名排序时仍在之后
+        let dir = self.backup_dir();
+        let c = Calendar::now();
+        let time = format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}",
+            c.year(),
+            c.month(),
+            c.day(),
+            c.hour(),
+            c.minute(),
+            c.second()
+        );
+        let mut path = dir.join(format!("{}-{}.{}", stem, time, ext));
+        let mut n = 1;
+        while path.exists() {
+            path = dir.join(format!("{}-{}-{}.{}", stem, time, n, ext));
+            n += 1;
+        }
+
+        self.backup_to(&path)?;
+        let keep = self
+            .config
+            .backup
+            .as_ref()
+            .and_then(|b| b.keep)
+            .unwrap_or(DEFAULT_BACKUP_KEEP);
+        self.prune_backups(keep)?;
+        Ok(path)
+    }
+
+    /// 备份目录下由 backup 生成的备份文件，按时间及同一秒内的序号从旧到新排列，目录不存在时为空
+    pub fn list_backups(&self) -> Result<Vec<PathBuf>, Error> {
+        let dir = self.backup_dir();
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let datasource = Path::new(&self.config.datasource);
+        let prefix = format!(
+            "{}-",
+            datasource.file_stem().unwrap_or_default().to_string_lossy()
+        );
+        let ext = datasource.extension().unwrap_or_default();
+
+        let mut list: Vec<((u64, u64, u64), PathBuf)> = fs::read_dir(&dir)
+            .map_err(|e| io_error("read dir", &dir, e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().unwrap_or_default() == ext)
+            .filter_map(|p| {
+                let stem = p.file_stem()?.to_string_lossy().into_owned();
+                let key = backup_key(stem.strip_prefix(&prefix)?)?;
+                Some((key, p))
+            })
+            .collect();
+        list.sort();
+        Ok(list.into_iter().map(|(_, p)| p).collect())
+    }
+
+    /// 只保留最近的 keep 个备份，返回删除的文件
+    pub fn prune_backups(&self, keep: usize) -> Result<Vec<PathBuf>, Error> {
+        let list = self.list_backups()?;
+        let count = list.len().saturating_sub(keep);
+        let removed: Vec<PathBuf> = list.into_iter().take(count).collect();
+        for path in &removed {
+            fs::remove_file(path).map_err(|e| io_error("remove", path, e))?;
+        }
+        Ok(removed)
+    }
+
+    /// 从备份文件恢复，覆盖当前数据库的全部内容。先检查备份文件的完整性，不完整时返回 SchemaError。
+    /// 恢复期间其它连接的操作会等待，恢复完成后其它连接读取到的是恢复后的数据。不能在事务中执行
+    pub fn restore_from(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(Error::NotFound(format!("backup {}", path.display())));
+        }
+        if self.depth.get() > 0 {
+            return Err(Error::ArgError("restore inside a transaction".to_string()));
+        }
+        let snapshot = Dao::open(Config {
+            datasource: path.to_string_lossy().into_owned(),
+            ..self.config.clone()
+        })?;
+        let problems = snapshot.integrity_check()?;
+        if !problems.is_empty() {
+            return Err(Error::SchemaError(format!(
+                "backup {} is corrupted: {}",
+                path.display(),
+                problems.join("; ")
+            )));
+        }
+        copy_database(&snapshot.connect, &self.connect)
+    }
+
+    /// 重建数据库文件，回收删除数据后的空间。需要与数据库同样大小的临时空间，执行期间阻塞其它连接的写入
+    pub fn vacuum(&self) -> Result<(), Error> {
+        self.batch("VACUUM")
+    }
+
+    /// 更新索引的统计信息，帮助查询优化器选择索引
+    pub fn analyze(&self) -> Result<(), Error> {
+        self.batch("ANALYZE")
+    }
+
+    /// 检查数据库文件的完整性，返回发现的问题，为空表示没有问题
+    pub fn integrity_check(&self) -> Result<Vec<String>, Error> {
+        let rows = self.query_json(&Query::new("PRAGMA integrity_check"))?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| row.get_str("integrity_check"))
+            .filter(|s| *s != "ok")
+            .map(|s| s.to_string())
+            .collect())
+    }
+}
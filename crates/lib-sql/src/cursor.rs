@@ -0,0 +1,122 @@
+use lib_json::{object::JsonObject, types::Type};
+
+use crate::{
+    traits::Error,
+    utils::{Condition, Operator, Search},
+};
+
+/// 将游标位置的排序字段值编码为不透明的字符串，客户端只需原样传回
+/// # Examples
+/// ```
+/// use lib_json::types::Type;
+/// use lib_sql::cursor::{decode, encode};
+///
+/// let values = vec![Type::I64(10), Type::String("a.b".to_string()), Type::Null];
+/// let cursor = encode(&values);
+/// assert_eq!(decode(&cursor).unwrap(), values);
+/// ```
+pub fn encode(values: &[Type]) -> String {
+    values
+        .iter()
+        .map(|value| {
+            let segment = match value {
+                Type::Null => "n".to_string(),
+                Type::Boolean(v) => format!("i{}", *v as i64),
+                Type::F32(v) => format!("f{}", v),
+                Type::F64(v) => format!("f{}", v),
+                Type::String(v) => format!("s{}", v),
+                Type::JsonObject(v) => format!("s{}", v.to_json()),
+                Type::JsonList(v) => format!("s{}", v.to_json()),
+                v => format!("i{}", v),
+            };
+            segment.bytes().map(|b| format!("{:02x}", b)).collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// 解码 encode 生成的游标，格式不正确时返回 ArgError
+pub fn decode(cursor: &str) -> Result<Vec<Type>, Error> {
+    let invalid = || Error::ArgError(format!("invalid cursor {}", cursor));
+    let mut values = vec![];
+    for segment in cursor.split('.') {
+        if segment.len() % 2 != 0 || !segment.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..segment.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&segment[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let segment = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (tag, payload) = segment.split_at_checked(1).ok_or_else(invalid)?;
+        let value = match tag {
+            "n" => Type::Null,
+            "i" => Type::I64(payload.parse().map_err(|_| invalid())?),
+            "f" => Type::F64(payload.parse().map_err(|_| invalid())?),
+            "s" => Type::String(payload.to_string()),
+            _ => return Err(invalid()),
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+// 游标分页使用的排序，在 search.sort 之后追加主键，保证顺序唯一
+fn keyset_sort(search: &Search, id: &str) -> Vec<(String, bool)> {
+    let mut keys = search.sort.keys().to_vec();
+    if !keys.iter().any(|(key, _)| key == id) {
+        keys.push((id.to_string(), false));
+    }
+    keys
+}
+
+/// 生成分页时实际执行的查询条件：补齐主键排序，未指定 start 时从头开始；设置了游标时追加
+/// `(k1 > v1) or (k1 = v1 and k2 > v2) ...` 形式的条件并忽略 start，倒序的字段使用 `<`。
+/// 排序字段的值为 null 时无法比较，游标分页的排序字段应不为空
+pub(crate) fn keyset_search(search: &Search, id: &str) -> Result<Search, Error> {
+    let keys = keyset_sort(search, id);
+    let mut keyset = search.clone();
+    keyset.sort.clear();
+    for (key, desc) in &keys {
+        keyset.sort.add(key, *desc);
+    }
+    if keyset.start < 0 {
+        keyset.start = 0;
+    }
+
+    let Some(cursor) = &search.cursor else {
+        return Ok(keyset);
+    };
+    let values = decode(cursor)?;
+    if values.len() != keys.len() {
+        return Err(Error::ArgError(format!(
+            "cursor {} does not match the sort keys",
+            cursor
+        )));
+    }
+    let mut any = vec![];
+    for i in 0..keys.len() {
+        let mut all: Vec<Condition> = keys[..i]
+            .iter()
+            .zip(&values)
+            .map(|((key, _), value)| Condition::new(key, Operator::Eq, value))
+            .collect();
+        let (key, desc) = &keys[i];
+        let op = if *desc { Operator::Lt } else { Operator::Gt };
+        all.push(Condition::new(key, op, &values[i]));
+        any.push(Condition::all(all));
+    }
+    keyset.matcher.and_cond(Condition::any(any));
+    keyset.start = 0;
+    Ok(keyset)
+}
+
+/// 根据本页最后一行生成下一页的游标，行中缺少排序字段时返回 None
+pub(crate) fn next_cursor(search: &Search, id: &str, last: &JsonObject) -> Option<String> {
+    let values = keyset_sort(search, id)
+        .iter()
+        .map(|(key, _)| last.get_data(key).cloned())
+        .collect::<Option<Vec<Type>>>()?;
+    Some(encode(&values))
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+
+use lib_json::{list::JsonList, types::Type};
+use serde::Deserialize;
+
+use crate::{
+    fts,
+    traits::{Error, Table},
+    utils::{Operator, Search, ToType},
+};
+
+/// 默认每页条数
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+/// 每页最大条数
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// 客户端提交的过滤值
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum FilterValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    List(Vec<FilterValue>),
+}
+
+impl ToType for FilterValue {
+    fn to_type(&self) -> Type {
+        match self {
+            FilterValue::Bool(v) => Type::Boolean(*v),
+            FilterValue::Int(v) => Type::I64(*v),
+            FilterValue::Float(v) => Type::F64(*v),
+            FilterValue::Text(v) => Type::String(v.clone()),
+            FilterValue::List(v) => {
+                Type::JsonList(JsonList::from_vec(v.iter().map(|v| v.to_type()).collect()))
+            }
+        }
+    }
+}
+
+/// 一个字段的过滤条件，可直接给值表示等于，或给出 操作符 -> 值
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum FieldFilter {
+    Ops(BTreeMap<String, FilterValue>),
+    Value(FilterValue),
+}
+
+/// 客户端提交的过滤、排序与分页条件，可以从查询字符串解析，也可以从 json 请求体反序列化：
+/// ```json
+/// {"filter": {"email": {"like": "foo"}, "id": {"in": [1, 2]}, "username": "demo"},
+///  "sort": "-created_at,id", "page": 2, "size": 20}
+/// ```
+/// 过滤操作符：eq、ne、gt、ge、lt、le、in、nin、like、nlike、starts、ends、glob、ieq、between、nbetween、null。
+/// in 与 between 的值可以是数组或逗号分隔的字符串，null 的值为 true 时表示为空，false 时表示不为空。
+/// 排序字段前加 `-` 表示倒序。after 为上一页返回的 next_cursor，设置后使用游标分页并忽略 page，
+/// count 为 false 时不统计总数。q 为全文检索的文本，按空白拆分后每个词都需要匹配，只能用于有 searchable 字段的表
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct SearchSpec {
+    #[serde(default)]
+    pub filter: BTreeMap<String, FieldFilter>,
+    pub sort: Option<String>,
+    pub page: Option<usize>,
+    pub size: Option<usize>,
+    pub after: Option<String>,
+    pub count: Option<bool>,
+    pub q: Option<String>,
+}
+
+impl SearchSpec {
+    /// 解析查询字符串，如 `filter[email][like]=foo&sort=-created_at&page=2&size=20`、
+    /// `sort=-created_at&size=20&after=<next_cursor>&count=false`，
+    /// `filter[email]=foo` 表示等于，其它参数忽略
+    /// # Examples
+    /// ```
+    /// use lib_sql::filter::SearchSpec;
+    /// use lib_sql::traits::Table;
+    ///
+    /// #[derive(Table)]
+    /// #[table(name = "users")]
+    /// struct User {
+    ///     #[id(auto_increment)]
+    ///     #[column(sortable)]
+    ///     id: i32,
+    ///     #[column(filterable, sortable)]
+    ///     email: String,
+    /// }
+    ///
+    /// let spec = SearchSpec::from_query("filter[email][like]=foo&sort=-id&page=2&size=10").unwrap();
+    /// let search = spec.to_search::<User>().unwrap();
+    /// assert_eq!((search.start, search.limit), (10, 10));
+    ///
+    /// // 不在白名单中的字段
+    /// let spec = SearchSpec::from_query("sort=password").unwrap();
+    /// assert!(spec.to_search::<User>().is_err());
+    /// ```
+    pub fn from_query(query: &str) -> Result<Self, Error> {
+        let mut spec = SearchSpec::default();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let (key, value) = (url_decode(key), url_decode(value));
+            match key.as_str() {
+                "sort" => spec.sort = Some(value),
+                "page" => spec.page = Some(parse_number("page", &value)?),
+                "size" => spec.size = Some(parse_number("size", &value)?),
+                "after" => spec.after = Some(value),
+                "count" => spec.count = Some(!matches!(value.as_str(), "false" | "0")),
+                "q" => spec.q = Some(value),
+                _ => {
+                    let Some(path) = key
+                        .strip_prefix("filter[")
+                        .and_then(|k| k.strip_suffix(']'))
+                    else {
+                        continue;
+                    };
+                    let (column, op) = path.split_once("][").unwrap_or((path, "eq"));
+                    let filter = spec
+                        .filter
+                        .entry(column.to_string())
+                        .or_insert_with(|| FieldFilter::Ops(BTreeMap::new()));
+                    if let FieldFilter::Ops(ops) = filter {
+                        ops.insert(op.to_string(), FilterValue::Text(value));
+                    }
+                }
+            }
+        }
+        Ok(spec)
+    }
+
+    /// 按 T 的白名单校验字段并转为 Search，字段不允许过滤或排序、操作符未知、分页参数不合法时返回 ArgError。
+    /// 总是带上分页条件，未指定时取第 1 页，每页 DEFAULT_PAGE_SIZE 条
+    pub fn to_search<T: Table>(&self) -> Result<Search, Error> {
+        let mut search = Search::default();
+
+        let filterable = T::filterable();
+        for (column, filter) in &self.filter {
+            let Some(column) = filterable.iter().find(|c| **c == column.as_str()) else {
+                return Err(Error::ArgError(format!(
+                    "column {} is not filterable",
+                    column
+                )));
+            };
+            let ops = match filter {
+                FieldFilter::Ops(ops) => ops.iter().map(|(op, v)| (op.as_str(), v)).collect(),
+                FieldFilter::Value(v) => vec![("eq", v)],
+            };
+            for (op, value) in ops {
+                let (op, value) = parse_op(op, value)?;
+                search.matcher.and(column, op, value);
+            }
+        }
+
+        // 全文检索，用户输入经过 quote 处理，不使用 FTS5 的语法
+        let text = self.q.as_deref().map(fts::quote).unwrap_or_default();
+        if !text.is_empty() {
+            if T::searchable().is_empty() {
+                return Err(Error::ArgError(format!(
+                    "{} does not support full-text search",
+                    T::table_name()
+                )));
+            }
+            search.matcher.and(T::table_name(), Operator::Match, text);
+        }
+
+        let sortable = T::sortable();
+        for key in self.sort.iter().flat_map(|s| s.split(',')) {
+            let key = key.trim();
+            if key.is_empty() {
+                continue;
+            }
+            let (key, desc) = match key.strip_prefix('-') {
+                Some(key) => (key, true),
+                None => (key.strip_prefix('+').unwrap_or(key), false),
+            };
+            let Some(column) = sortable.iter().find(|c| **c == key) else {
+                return Err(Error::ArgError(format!("column {} is not sortable", key)));
+            };
+            search.sort.add(column, desc);
+        }
+
+        let page = self.page.unwrap_or(1);
+        let size = self.size.unwrap_or(DEFAULT_PAGE_SIZE);
+        if page == 0 {
+            return Err(Error::ArgError("page starts from 1".to_string()));
+        }
+        if size == 0 || size > MAX_PAGE_SIZE {
+            return Err(Error::ArgError(format!(
+                "size must be between 1 and {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+        search.start = (page - 1)
+            .checked_mul(size)
+            .and_then(|start| isize::try_from(start).ok())
+            .ok_or_else(|| Error::ArgError("page out of range".to_string()))?;
+        search.limit = size as isize;
+        if let Some(cursor) = &self.after {
+            search.after(cursor);
+        }
+        search.skip_total = self.count == Some(false);
+        Ok(search)
+    }
+}
+
+// 将操作符名称转为 Operator 及绑定的值
+fn parse_op(op: &str, value: &FilterValue) -> Result<(Operator, Type), Error> {
+    let op = match op {
+        "eq" => Operator::Eq,
+        "ne" => Operator::Ne,
+        "gt" => Operator::Gt,
+        "ge" => Operator::Ge,
+        "lt" => Operator::Lt,
+        "le" => Operator::Le,
+        "like" => Operator::Like,
+        "nlike" => Operator::NotLike,
+        "starts" => Operator::StartsWith,
+        "ends" => Operator::EndsWith,
+        "glob" => Operator::Glob,
+        "ieq" => Operator::EqNoCase,
+        "in" | "nin" | "between" | "nbetween" => {
+            let list = match value {
+                FilterValue::List(list) => list.clone(),
+                FilterValue::Text(text) => text
+                    .split(',')
+                    .map(|v| FilterValue::Text(v.trim().to_string()))
+                    .collect(),
+                v => vec![v.clone()],
+            };
+            let op = match op {
+                "in" => Operator::In,
+                "nin" => Operator::NotIn,
+                "between" => Operator::Between,
+                _ => Operator::NotBetween,
+            };
+            if matches!(op, Operator::Between | Operator::NotBetween) && list.len() != 2 {
+                return Err(Error::ArgError(format!("{} expects two values", op)));
+            }
+            return Ok((op, list.to_type()));
+        }
+        "null" => {
+            let is_null = match value {
+                FilterValue::Bool(v) => *v,
+                FilterValue::Int(v) => *v != 0,
+                FilterValue::Text(v) => matches!(v.as_str(), "true" | "1" | ""),
+                _ => return Err(Error::ArgError("null expects a boolean".to_string())),
+            };
+            let op = if is_null {
+                Operator::IsNull
+            } else {
+                Operator::IsNotNull
+            };
+            return Ok((op, Type::Null));
+        }
+        _ => return Err(Error::ArgError(format!("unknown filter operator {}", op))),
+    };
+    if let FilterValue::List(_) = value {
+        return Err(Error::ArgError(format!("{} expects a single value", op)));
+    }
+    Ok((op, value.to_type()))
+}
+
+fn parse_number(key: &str, value: &str) -> Result<usize, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::ArgError(format!("{} must be a positive integer", key)))
+}
+
+// 解码查询字符串中的 %XX 与 +
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            // % 后不是两位十六进制数时保留原样，from_str_radix 会接受 +1 这样带符号的写法
+            b'%' if i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit() =>
+            {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                out.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
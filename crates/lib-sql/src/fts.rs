@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use lib_json::types::Type;
+use sqlite::Connection;
+
+use crate::{
+    interface::{live_matcher, where_clause},
+    sources::Dao,
+    traits::{CommInterface, Error, Table},
+    utils::{Matcher, Query},
+};
+
+// 检索结果中 bm25 得分的字段，使用 __ 开头避免与实体的字段重名
+const RANK: &str = "__rank";
+
+/// 全文检索使用的 FTS5 虚拟表名，为 {表名}_fts
+pub fn fts_table<T: Table>() -> String {
+    format!("{}_fts", T::table_name())
+}
+
+/// 根据 Table::searchable 生成 FTS5 虚拟表及同步数据的触发器。
+/// 虚拟表以原表为外部内容表，不重复保存文本，按 rowid 与原表关联，
+/// 原表应使用整数主键（INTEGER PRIMARY KEY），此时 rowid 即为主键
+/// # Examples
+/// ```
+/// use lib_sql::fts::create_fts_sql;
+/// use lib_sql::traits::Table;
+///
+/// #[derive(Table)]
+/// #[table(name = "posts")]
+/// struct Post {
+///     #[id(auto_increment)]
+///     id: i64,
+///     #[column(searchable)]
+///     title: String,
+///     #[column(searchable)]
+///     body: String,
+/// }
+///
+/// let sql = create_fts_sql::<Post>();
+/// assert_eq!(
+///     sql[0],
+///     "CREATE VIRTUAL TABLE IF NOT EXISTS posts_fts USING fts5(title, body, content='posts')"
+/// );
+/// assert_eq!(sql.len(), 4);
+/// ```
+pub fn create_fts_sql<T: Table>() -> Vec<String> {
+    let (table, fts) = (T::table_name(), fts_table::<T>());
+    let columns = T::searchable().join(", ");
+    let values = |row: &str| {
+        T::searchable()
+            .iter()
+            .map(|c| format!("{}.{}", row, c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let insert = format!(
+        "INSERT INTO {fts}(rowid, {columns}) VALUES (new.rowid, {});",
+        values("new")
+    );
+    let delete = format!(
+        "INSERT INTO {fts}({fts}, rowid, {columns}) VALUES ('delete', old.rowid, {});",
+        values("old")
+    );
+    vec![
+        format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {fts} USING fts5({columns}, content='{table}')"
+        ),
+        format!("CREATE TRIGGER IF NOT EXISTS {fts}_ai AFTER INSERT ON {table} BEGIN {insert} END"),
+        format!("CREATE TRIGGER IF NOT EXISTS {fts}_ad AFTER DELETE ON {table} BEGIN {delete} END"),
+        // 只在检索字段变化时更新索引
+        format!(
+            "CREATE TRIGGER IF NOT EXISTS {fts}_au AFTER UPDATE OF {columns} ON {table} \
+             BEGIN {delete} {insert} END"
+        ),
+    ]
+}
+
+/// 删除 FTS5 虚拟表及触发器的语句
+pub fn drop_fts_sql<T: Table>() -> Vec<String> {
+    let fts = fts_table::<T>();
+    vec![
+        format!("DROP TRIGGER IF EXISTS {}_ai", fts),
+        format!("DROP TRIGGER IF EXISTS {}_ad", fts),
+        format!("DROP TRIGGER IF EXISTS {}_au", fts),
+        format!("DROP TABLE IF EXISTS {}", fts),
+    ]
+}
+
+/// 将用户输入的文本转为 FTS5 查询语句：按空白拆分，每个词作为短语加上双引号，
+/// 词之间为 and 关系。避免输入中的引号、括号、AND/OR 等被当作 FTS5 语法而报错
+/// # Examples
+/// ```
+/// use lib_sql::fts::quote;
+///
+/// assert_eq!(quote(r#"rust "orm" OR"#), r#""rust" """orm""" "OR""#);
+/// assert_eq!(quote("  "), "");
+/// ```
+pub fn quote(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// 检索结果中额外输出的片段
+#[derive(Debug, Clone)]
+enum Mark {
+    /// 完整的字段内容，匹配的词加上标记
+    Highlight(String),
+    /// 匹配位置附近的片段，最多 tokens 个词
+    Snippet(String, usize),
+}
+
+/// 全文检索的条件，结果按 bm25 相关度排序，可以输出匹配词加上标记的字段内容或片段
+/// # Examples
+/// ```no_run
+/// use lib_sql::fts::TextSearch;
+/// use lib_sql::sources::Dao;
+/// use lib_sql::utils::{Matcher, Operator};
+///
+/// let dao = Dao::new().unwrap();
+/// let mut matcher = Matcher::new();
+/// matcher.and("status", Operator::Eq, 1);
+/// let search = TextSearch::new("rust orm")
+///     .highlight("title")
+///     .snippet("body", 16)
+///     .filter(matcher)
+///     .limit(0, 20);
+/// // let hits = dao.search_text::<Post>(&search)?;
+/// // hits[0].entity, hits[0].rank, hits[0].highlights["title"], hits[0].snippets["body"]
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextSearch {
+    query: String,
+    matcher: Matcher,
+    marks: Vec<Mark>,
+    open: String,
+    close: String,
+    ellipsis: String,
+    start: isize,
+    limit: isize,
+}
+
+impl TextSearch {
+    /// query 为 FTS5 查询语句，用户输入的文本可以先经过 quote 处理
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            matcher: Matcher::new(),
+            marks: vec![],
+            open: "<b>".to_string(),
+            close: "</b>".to_string(),
+            ellipsis: "...".to_string(),
+            start: -1,
+            limit: -1,
+        }
+    }
+
+    /// 输出 column 的完整内容，匹配的词加上标记
+    pub fn highlight(mut self, column: &str) -> Self {
+        self.marks.push(Mark::Highlight(column.to_string()));
+        self
+    }
+
+    /// 输出 column 中匹配位置附近的片段，最多 tokens 个词（1 至 64）
+    pub fn snippet(mut self, column: &str, tokens: usize) -> Self {
+        self.marks
+            .push(Mark::Snippet(column.to_string(), tokens.clamp(1, 64)));
+        self
+    }
+
+    /// 匹配词的标记，默认为 `<b>` 与 `</b>`
+    pub fn marker(mut self, open: &str, close: &str) -> Self {
+        self.open = open.to_string();
+        self.close = close.to_string();
+        self
+    }
+
+    /// 片段省略部分的标记，默认为 `...`
+    pub fn ellipsis(mut self, ellipsis: &str) -> Self {
+        self.ellipsis = ellipsis.to_string();
+        self
+    }
+
+    /// 原表上的过滤条件，默认排除已软删除的数据
+    pub fn filter(mut self, matcher: Matcher) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// 分页，同 Search 的 start 与 limit
+    pub fn limit(mut self, start: isize, limit: isize) -> Self {
+        self.start = start;
+        self.limit = limit;
+        self
+    }
+
+    /// 解析出查询语句，FTS5 的查询在子查询中执行，子查询的字段均以 __ 开头，不与原表的字段冲突
+    fn parse<T: Table>(&self) -> Result<Query, Error> {
+        let (table, fts) = (T::table_name(), fts_table::<T>());
+        let searchable = T::searchable();
+        let mut fields = vec![format!("bm25({}) as {}", fts, RANK)];
+        let mut params = vec![];
+        for (i, mark) in self.marks.iter().enumerate() {
+            let column = match mark {
+                Mark::Highlight(column) | Mark::Snippet(column, _) => column,
+            };
+            let Some(index) = searchable.iter().position(|c| c == column) else {
+                return Err(Error::ArgError(format!(
+                    "column {} is not searchable in {}",
+                    column, table
+                )));
+            };
+            match mark {
+                Mark::Highlight(_) => {
+                    fields.push(format!(
+                        "highlight({}, {}, ?, ?) as __mark_{}",
+                        fts, index, i
+                    ));
+                    params.extend([
+                        Type::String(self.open.clone()),
+                        Type::String(self.close.clone()),
+                    ]);
+                }
+                Mark::Snippet(_, tokens) => {
+                    fields.push(format!(
+                        "snippet({}, {}, ?, ?, ?, {}) as __mark_{}",
+                        fts, index, tokens, i
+                    ));
+                    params.extend([
+                        Type::String(self.open.clone()),
+                        Type::String(self.close.clone()),
+                        Type::String(self.ellipsis.clone()),
+                    ]);
+                }
+            }
+        }
+        params.push(Type::String(self.query.clone()));
+
+        let marks: Vec<String> = (0..self.marks.len())
+            .map(|i| format!(",__fts.__mark_{}", i))
+            .collect();
+        let mut query = Query::with_params(
+            &format!(
+                "select {table}.*,__fts.{RANK}{} from {table} join \
+                 (select rowid as __fts_rowid,{} from {fts} where {fts} match ?) __fts \
+                 on {table}.rowid = __fts.__fts_rowid",
+                marks.join(""),
+                fields.join(","),
+            ),
+            params,
+        );
+        query.append(where_clause(&live_matcher::<T>(&self.matcher)));
+        query.push_sql(&format!(" order by __fts.{}", RANK));
+        if self.start > -1 && self.limit > -1 {
+            query.push_sql(&format!(" limit {},{}", self.start, self.limit));
+        }
+        Ok(query)
+    }
+}
+
+/// 全文检索的一条结果
+#[derive(Debug, Clone)]
+pub struct TextHit<T> {
+    pub entity: T,
+    /// bm25 相关度，越小越相关
+    pub rank: f64,
+    /// 以字段名为 key 的 highlight 结果
+    pub highlights: HashMap<String, String>,
+    /// 以字段名为 key 的 snippet 结果
+    pub snippets: HashMap<String, String>,
+}
+
+// 为 sqlite 实现全文检索相关的接口
+impl Dao<Connection> {
+    /// 创建 T 的 FTS5 虚拟表与触发器，并为已有的数据建立索引。虚拟表已存在时不做处理
+    pub fn create_fts_for<T: Table>(&self) -> Result<(), Error> {
+        if T::searchable().is_empty() {
+            return Err(Error::ArgError(format!(
+                "{} has no searchable column",
+                T::table_name()
+            )));
+        }
+        let mut query = Query::new("select name from sqlite_master where type='table' and name=?");
+        query.bind(fts_table::<T>());
+        if !self.query_json(&query)?.is_empty() {
+            return Ok(());
+        }
+        self.transaction(|tx| {
+            for sql in create_fts_sql::<T>() {
+                tx.batch(&sql)?;
+            }
+            tx.rebuild_fts::<T>()
+        })
+    }
+
+    /// 删除 T 的 FTS5 虚拟表与触发器
+    pub fn drop_fts_for<T: Table>(&self) -> Result<(), Error> {
+        for sql in drop_fts_sql::<T>() {
+            self.batch(&sql)?;
+        }
+        Ok(())
+    }
+
+    /// 按原表的数据重建索引，用于触发器建立之前已有的数据，或索引与原表不一致时
+    pub fn rebuild_fts<T: Table>(&self) -> Result<(), Error> {
+        let fts = fts_table::<T>();
+        self.batch(&format!("INSERT INTO {0}({0}) VALUES ('rebuild')", fts))
+    }
+
+    /// 全文检索，结果按相关度排序。需要先建立全文索引，见 create_fts_for。
+    /// 查询语句有语法错误时返回 SqlError，用户输入的文本可以先经过 quote 处理
+    pub fn search_text<T: Table>(&self, search: &TextSearch) -> Result<Vec<TextHit<T>>, Error> {
+        let query = search.parse::<T>()?;
+        let mut hits = vec![];
+        for row in self.query_json(&query)? {
+            let mut entity = T::from_json_object(&row)?;
+            entity.after_load()?;
+            let mut hit = TextHit {
+                entity,
+                rank: row.get_f64(RANK).unwrap_or_default(),
+                highlights: HashMap::new(),
+                snippets: HashMap::new(),
+            };
+            for (i, mark) in search.marks.iter().enumerate() {
+                let text = match row.get_data(&format!("__mark_{}", i)) {
+                    Some(Type::String(text)) => text.clone(),
+                    _ => String::new(),
+                };
+                match mark {
+                    Mark::Highlight(column) => hit.highlights.insert(column.clone(), text),
+                    Mark::Snippet(column, _) => hit.snippets.insert(column.clone(), text),
+                };
+            }
+            hits.push(hit);
+        }
+        Ok(hits)
+    }
+}
@@ -1,177 +1,767 @@
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+
+use crate::cursor;
+use crate::observer::QueryEvent;
+use crate::relation::Relation;
 use crate::sources::Dao;
 
-use crate::traits::{CommInterface, Error, Table};
-use crate::utils::Search;
+use crate::traits::{CommInterface, Error, SqlError, Table, TransactionMode};
+use crate::utils::{Matcher, Operator, Query, Search, ToType, now, type_text};
 use lib_json::object::JsonObject;
 use lib_json::types::*;
-use sqlite::{Connection, State, Statement};
+use sqlite::{Connection, State, Statement, Value};
 
 // 为 sqlite 实现通用接口
 impl CommInterface for Dao<Connection> {
     fn list<T: Table>(&self, search: &mut Search) -> Result<Vec<T>, Error> {
         let table_name = T::table_name();
+        let mut scoped = search.clone();
+        scoped.matcher = live_matcher::<T>(&search.matcher);
 
         // 查询总数
         if search.is_seach_total() {
-            let mut count = search.clone();
+            let mut count = scoped.clone();
             count.start = 0;
             count.limit = 1;
             count.fileds = "count(*) as cnt".to_string();
             let count = count.parse(table_name);
-            println!("count sql={}", count);
-            let statement = self.connect.prepare(&count);
-            if statement.is_err() {
-                if let Err(e) = statement {
-                    return Err(Error::SqlError(format!("sql error:{};{:?}", count, e)));
-                }
-            }
-            let mut statement = statement.unwrap();
-            while let Ok(State::Row) = statement.next() {
-                let cnt = statement.read::<i64, _>("cnt").unwrap();
-                search.total = cnt;
-                break;
-            }
+            search.total = self
+                .query_json(&count)?
+                .first()
+                .and_then(|row| row.get_i64("cnt"))
+                .unwrap_or_default();
         }
 
-        // 查列表数据
-        let mut query = format!("SELECT * FROM {}", table_name);
-        let condition = search.parse(table_name);
+        // 查列表数据，分页时按主键补齐排序，保证顺序稳定并可生成下一页的游标
+        let mut query = Query::new(&format!("SELECT * FROM {}", table_name));
+        let paged = search.limit > -1;
+        let condition = if paged {
+            cursor::keyset_search(&scoped, T::id())?.parse(table_name)
+        } else {
+            scoped.parse(table_name)
+        };
         if !condition.is_empty() {
             query = condition;
-            println!("query={}", query);
         }
 
-        let statement = self.connect.prepare(&query);
-        if let Err(e) = statement {
-            return Err(Error::SqlError(format!("sql error:{};{:?}", query, e)));
+        let list: Vec<T> = self.fetch(&query)?;
+        search.next_cursor = None;
+        if let Some(last) = list.last()
+            && paged
+            && list.len() == search.limit as usize
+        {
+            search.next_cursor = cursor::next_cursor(search, T::id(), &last.to_json_object()?);
         }
-        let mut statement = statement.unwrap();
-        read_sqlite_row(&mut statement)
+        Ok(list)
     }
 
     fn list_all<T: Table>(&self) -> Result<Vec<T>, Error> {
-        let query = format!("SELECT * FROM {}", T::table_name());
-        let statement = self.connect.prepare(&query);
-        // statement.bind((1, 50)).unwrap();
-        if let Err(e) = statement {
-            return Err(Error::SqlError(format!("sql error:{};{:?}", query, e)));
-        }
-        let mut statement = statement.unwrap();
-        read_sqlite_row(&mut statement)
+        let mut query = Query::new(&format!("SELECT * FROM {}", T::table_name()));
+        query.append(where_clause(&live_matcher::<T>(&Matcher::new())));
+        self.fetch(&query)
     }
 
-    fn set<T: Table>(&self, entity: T) -> Result<usize, Error> {
+    fn set<T: Table>(&self, mut entity: T) -> Result<usize, Error> {
+        entity.before_update()?;
         let mut update = vec![];
-        let object = entity.to_json_object().unwrap();
+        let mut query = Query::default();
+        let mut object = entity.to_json_object()?;
+        stamp_update::<T>(&mut object, now());
         for column in T::columns() {
+            // 创建时间只在添加时写入，版本号由数据库递增，删除时间只由 delete 与 restore 修改
+            if Some(column) == T::created_at_column()
+                || Some(column) == T::version_column()
+                || Some(column) == T::soft_delete_column()
+            {
+                continue;
+            }
             if let Some(v) = object.get_data(column) {
-                match v {
-                    Type::String(v) => update.push(format!("{}='{}'", column, v)),
-                    Type::Boolean(v) => update.push(format!("{}={}", column, v)),
-                    Type::JsonObject(v) => update.push(format!("{}='{}'", column, v.to_json())),
-                    Type::JsonList(v) => update.push(format!("{}='{}'", column, v.to_json())),
-                    Type::Null => update.push(format!("{}=NULL", column)),
-                    _ => update.push(format!("{}={}", column, v)),
-                }
+                update.push(format!("{}=?", column));
+                query.bind(v);
             }
         }
 
         if update.is_empty() {
             return Ok(0);
         }
-        let sql = format!(
-            "update {} set {} where {}={}",
+        if let Some(version) = T::version_column() {
+            update.push(format!("{0}={0}+1", version));
+        }
+        query.push_sql(&format!(
+            "update {} set {} where {}=?",
             T::table_name(),
             update.join(","),
             T::id(),
-            entity.id_value()
-        );
-        println!("set sql={}", sql);
-        match self.connect.execute(&sql) {
-            Ok(_) => Ok(self.connect.change_count()),
-            Err(e) => Err(Error::SqlError(format!("{};{}", sql, e))),
+        ));
+        query.bind(id_param(&entity, &object));
+        // 已软删除的数据不修改
+        if let Some(column) = T::soft_delete_column() {
+            query.push_sql(&format!(" and {} is null", column));
+        }
+
+        let Some(version) = T::version_column() else {
+            return self.execute(&query);
+        };
+        query.push_sql(&format!(" and {}=?", version));
+        query.bind(object.get_data(version).cloned().unwrap_or(Type::Null));
+        match self.execute(&query)? {
+            0 => Err(Error::Conflict(format!(
+                "{} {} was modified or deleted",
+                T::table_name(),
+                entity.id_value()
+            ))),
+            n => Ok(n),
         }
     }
 
     fn delete<T: Table>(&self, entity: T) -> Result<usize, Error> {
-        let sql = format!(
-            "delete from {} where {}={}",
-            T::table_name(),
-            T::id(),
-            entity.id_value()
-        );
-        println!("del sql={}", sql);
-        match self.connect.execute(&sql) {
-            Ok(_) => Ok(self.connect.change_count()),
-            Err(e) => Err(Error::SqlError(format!("{};{}", sql, e))),
-        }
+        entity.before_delete()?;
+        let object = entity.to_json_object()?;
+        self.delete_by_id::<T, _>(id_param(&entity, &object))
     }
 
     fn add<T: Table>(&self, entity: T) -> Result<usize, Error> {
-        let mut columns = vec![];
-        let mut values = vec![];
-        let object = entity.to_json_object().unwrap();
-        for column in T::columns() {
-            if column == T::id() && T::id_auto_increase() { // 需要自增的id字段不处理
-                continue;
-            }
-            if let Some(v) = object.get_data(column) {
-                match v {
-                    _ => {
-                        columns.push(format!("`{}`", column));
-                        values.push(format!("{}", v.to_json()));
+        let object = insert_object(entity, now())?;
+        let columns = insert_columns::<T>(&object);
+        let query = insert_query::<T>(&columns, &object);
+        self.execute(&query)
+    }
+
+    fn insert<T: Table>(&self, entity: T) -> Result<i64, Error> {
+        let object = insert_object(entity, now())?;
+        let columns = insert_columns::<T>(&object);
+        let query = insert_query::<T>(&columns, &object);
+        self.execute(&query)?;
+        self.last_insert_rowid()
+    }
+
+    fn insert_and_reload<T: Table>(&self, entity: T) -> Result<T, Error> {
+        let rowid = self.insert(entity)?;
+        let mut query = Query::new(&format!("SELECT * FROM {} WHERE rowid=?", T::table_name()));
+        query.bind(rowid);
+        match self.fetch(&query)?.pop() {
+            Some(entity) => Ok(entity),
+            None => Err(Error::NotFound(format!("{} rowid {}", T::table_name(), rowid))),
+        }
+    }
+
+    fn add_many<T: Table>(&self, entities: Vec<T>) -> Result<Vec<i64>, Error> {
+        let now = now();
+        let objects = entities
+            .into_iter()
+            .map(|e| insert_object(e, now))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(first) = objects.first() else {
+            return Ok(vec![]);
+        };
+        let columns = insert_columns::<T>(first);
+        let sql = insert_query::<T>(&columns, first).sql;
+
+        // 整批作为一次执行通知 observer
+        let params = columns.len() * objects.len();
+        self.transaction(|tx| {
+            tx.observe(&sql, params, Vec::len, || {
+                let mut statement = tx.prepare(&Query::new(&sql))?;
+                let mut ids = Vec::with_capacity(objects.len());
+                for object in &objects {
+                    if let Err(e) = statement.reset() {
+                        return Err(sql_error(e, &sql));
+                    }
+                    for (i, column) in columns.iter().enumerate() {
+                        let value = object.get_data(column).unwrap_or(&Type::Null);
+                        if let Err(e) = statement.bind((i + 1, to_sqlite_value(value))) {
+                            return Err(sql_error(e, &sql));
+                        }
+                    }
+                    loop {
+                        match statement.next() {
+                            Ok(State::Row) => continue,
+                            Ok(State::Done) => break,
+                            Err(e) => return Err(sql_error(e, &sql)),
+                        }
                     }
+                    ids.push(tx.last_insert_rowid()?);
                 }
-            }
+                Ok(ids)
+            })
+        })
+    }
+
+    fn get<T: Table, I: ToType>(&self, id: I) -> Result<Option<T>, Error> {
+        let mut matcher = Matcher::new();
+        matcher.and(T::id(), Operator::Eq, id);
+        self.find_one(&matcher)
+    }
+
+    fn find_one<T: Table>(&self, matcher: &Matcher) -> Result<Option<T>, Error> {
+        let mut query = Query::new(&format!("SELECT * FROM {}", T::table_name()));
+        query.append(where_clause(&live_matcher::<T>(matcher))).push_sql(" LIMIT 1");
+        Ok(self.fetch(&query)?.pop())
+    }
+
+    fn exists<T: Table>(&self, matcher: &Matcher) -> Result<bool, Error> {
+        let mut query = Query::new(&format!("SELECT 1 FROM {}", T::table_name()));
+        query.append(where_clause(&live_matcher::<T>(matcher))).push_sql(" LIMIT 1");
+        Ok(!self.query_json(&query)?.is_empty())
+    }
+
+    fn count<T: Table>(&self, matcher: &Matcher) -> Result<i64, Error> {
+        let mut query = Query::new(&format!("SELECT count(*) AS cnt FROM {}", T::table_name()));
+        query.append(where_clause(&live_matcher::<T>(matcher)));
+        Ok(self
+            .query_json(&query)?
+            .first()
+            .and_then(|row| row.get_i64("cnt"))
+            .unwrap_or_default())
+    }
+
+    fn delete_by_id<T: Table, I: ToType>(&self, id: I) -> Result<usize, Error> {
+        let mut matcher = Matcher::new();
+        matcher.and(T::id(), Operator::Eq, id);
+        self.remove::<T>(&matcher)
+    }
+
+    fn delete_where<T: Table>(&self, matcher: &Matcher) -> Result<usize, Error> {
+        if matcher.is_empty() {
+            return Err(Error::ArgError(format!(
+                "delete from {} without condition",
+                T::table_name()
+            )));
         }
+        self.remove::<T>(matcher)
+    }
+
+    fn restore<T: Table, I: ToType>(&self, id: I) -> Result<usize, Error> {
+        let Some(column) = T::soft_delete_column() else {
+            return Err(Error::ArgError(format!(
+                "table {} does not use soft delete",
+                T::table_name()
+            )));
+        };
+        let mut matcher = Matcher::new();
+        matcher.with_deleted().and(T::id(), Operator::Eq, id);
+        matcher.and(column, Operator::IsNotNull, ());
+        let mut values = JsonObject::new();
+        values.set_null(column);
+        self.update_where::<T>(&matcher, values)
+    }
+
+    fn purge<T: Table, I: ToType>(&self, id: I) -> Result<usize, Error> {
+        let mut query = Query::new(&format!(
+            "delete from {} where {}=?",
+            T::table_name(),
+            T::id(),
+        ));
+        query.bind(id);
+        self.execute(&query)
+    }
+
+    fn upsert<T: Table>(&self, entity: T, conflict_columns: &[&str]) -> Result<usize, Error> {
+        let conflict_columns = match conflict_columns {
+            [] => vec![T::id()],
+            columns => columns.to_vec(),
+        };
+        check_columns::<T>(&conflict_columns)?;
 
+        let object = insert_object(entity, now())?;
+        let mut columns = insert_columns::<T>(&object);
+        // 以自增主键作为冲突字段时需要写入主键
+        if conflict_columns.contains(&T::id())
+            && !columns.contains(&T::id())
+            && object.get_data(T::id()).is_some()
+        {
+            columns.insert(0, T::id());
+        }
         if columns.is_empty() {
-            println!("no columns found");
+            return Err(Error::ArgError(format!(
+                "upsert into {} without columns",
+                T::table_name()
+            )));
+        }
+
+        let mut query = insert_query::<T>(&columns, &object);
+        // 冲突时保留原有的创建时间，删除时间只由 delete 与 restore 修改
+        let update: Vec<String> = columns
+            .iter()
+            .filter(|c| !conflict_columns.contains(c))
+            .filter(|c| ![T::created_at_column(), T::soft_delete_column()].contains(&Some(**c)))
+            .map(|c| match T::version_column() {
+                Some(version) if version == *c => format!("`{0}`=`{0}`+1", c),
+                _ => format!("`{0}`=excluded.`{0}`", c),
+            })
+            .collect();
+        query.push_sql(&format!(" on conflict({})", conflict_columns.join(",")));
+        if update.is_empty() {
+            query.push_sql(" do nothing");
+            return self.execute(&query);
+        }
+        query.push_sql(&format!(" do update set {}", update.join(",")));
+
+        // 与 set 相同，不修改已软删除的数据，版本号与数据库中的不一致时不修改
+        let table = T::table_name();
+        let mut conditions = vec![];
+        if let Some(column) = T::soft_delete_column() {
+            conditions.push(format!("{}.`{}` is null", table, column));
+        }
+        let version = T::version_column().filter(|v| columns.contains(v));
+        if let Some(version) = version {
+            conditions.push(format!("{0}.`{1}`=excluded.`{1}`", table, version));
+        }
+        if !conditions.is_empty() {
+            query.push_sql(&format!(" where {}", conditions.join(" and ")));
+        }
+        match self.execute(&query)? {
+            0 if version.is_some() => Err(Error::Conflict(format!(
+                "{} on conflict({}) was modified or deleted",
+                table,
+                conflict_columns.join(",")
+            ))),
+            n => Ok(n),
+        }
+    }
+
+    fn update_where<T: Table>(
+        &self,
+        matcher: &Matcher,
+        mut values: JsonObject,
+    ) -> Result<usize, Error> {
+        if matcher.is_empty() {
+            return Err(Error::ArgError(format!(
+                "update {} without condition",
+                T::table_name()
+            )));
+        }
+        check_columns::<T>(&values.keys())?;
+        if values.is_empty() {
             return Ok(0);
         }
-        let sql = format!(
-            "insert into {} ({}) values ({})",
-            T::table_name(),
-            columns.join(","),
-            values.join(",")
-        );
-        println!("add sql={}，", sql);
-        match self.connect.execute(&sql) {
-            Ok(_) => Ok(self.connect.change_count()),
-            Err(e) => Err(Error::SqlError(format!("{};{}", sql, e))),
+        stamp_update::<T>(&mut values, now());
+        let columns = values.keys();
+
+        let mut query = Query::new(&format!("update {} set ", T::table_name()));
+        let mut update = vec![];
+        for column in &columns {
+            update.push(format!("`{}`=?", column));
+            if let Some(v) = values.get_data(column) {
+                query.bind(v);
+            }
+        }
+        if let Some(version) = T::version_column().filter(|v| !columns.contains(v)) {
+            update.push(format!("`{0}`=`{0}`+1", version));
+        }
+        query.push_sql(&update.join(","));
+        query.append(where_clause(&live_matcher::<T>(matcher)));
+        self.execute(&query)
+    }
+
+    fn aggregate<T: Table>(&self, search: &Search) -> Result<Vec<JsonObject>, Error> {
+        let mut scoped = search.clone();
+        scoped.matcher = live_matcher::<T>(&search.matcher);
+        self.query_json(&scoped.parse(T::table_name()))
+    }
+
+    fn load_related<P: Table, C: Table>(
+        &self,
+        parents: &[P],
+        relation: &Relation,
+    ) -> Result<Vec<Vec<C>>, Error> {
+        let (parent_key, child_key) = (relation.parent_key::<P>(), relation.child_key::<C>());
+        match relation {
+            Relation::BelongsTo { .. } => check_columns::<P>(&[parent_key])?,
+            Relation::HasMany { .. } => check_columns::<C>(&[child_key])?,
+            Relation::ManyToMany { .. } => {}
+        }
+
+        // 每个父实体的关联字段值，为 null 时没有关联的数据
+        let mut keys = vec![];
+        let mut seen = HashSet::new();
+        let mut params: Vec<Type> = vec![];
+        for parent in parents {
+            let key = parent
+                .to_json_object()?
+                .get_data(parent_key)
+                .filter(|v| !matches!(v, Type::Null))
+                .cloned();
+            if let Some(v) = &key
+                && seen.insert(type_text(v))
+            {
+                params.push(v.clone());
+            }
+            keys.push(key);
+        }
+        if params.is_empty() {
+            return Ok(parents.iter().map(|_| vec![]).collect());
+        }
+
+        // 按关联字段值分组，属于关系中多个父实体可能关联同一条数据，因此分别转为实体
+        let mut groups: HashMap<String, Vec<JsonObject>> = HashMap::new();
+        for row in self.query_json(&relation.load_query::<C>(params))? {
+            if let Some(v) = row.get_data(child_key) {
+                groups.entry(type_text(v)).or_default().push(row);
+            }
+        }
+        keys.iter()
+            .map(|key| {
+                let rows = key
+                    .as_ref()
+                    .and_then(|k| groups.get(&type_text(k)))
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                rows.iter()
+                    .map(|row| {
+                        let mut entity = C::from_json_object(row)?;
+                        entity.after_load()?;
+                        Ok(entity)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn transaction_with<R, F>(&self, mode: TransactionMode, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&Self) -> Result<R, Error>,
+    {
+        let depth = self.depth.get();
+        // 最外层使用 BEGIN，嵌套的使用 savepoint
+        let (begin, commit, rollback) = if depth == 0 {
+            let begin = match mode {
+                TransactionMode::Deferred => "BEGIN DEFERRED",
+                TransactionMode::Immediate => "BEGIN IMMEDIATE",
+                TransactionMode::Exclusive => "BEGIN EXCLUSIVE",
+            };
+            (begin.to_string(), "COMMIT".to_string(), "ROLLBACK".to_string())
+        } else {
+            let name = format!("sp_{}", depth);
+            (
+                format!("SAVEPOINT {}", name),
+                format!("RELEASE {}", name),
+                format!("ROLLBACK TO {0}; RELEASE {0}", name),
+            )
+        };
+
+        self.batch(&begin)?;
+        self.depth.set(depth + 1);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        self.depth.set(depth);
+
+        match result {
+            Ok(Ok(value)) => match self.batch(&commit) {
+                Ok(_) => Ok(value),
+                Err(e) => {
+                    let _ = self.batch(&rollback);
+                    Err(e)
+                }
+            },
+            Ok(Err(e)) => {
+                let _ = self.batch(&rollback);
+                Err(e)
+            }
+            Err(cause) => {
+                let _ = self.batch(&rollback);
+                panic::resume_unwind(cause)
+            }
         }
     }
 }
 
+// 使用软删除的表，未指定 with_deleted 时只匹配未删除的数据
+pub(crate) fn live_matcher<T: Table>(matcher: &Matcher) -> Matcher {
+    scoped_matcher::<T>(matcher, false)
+}
+
+// 同 live_matcher，软删除条件带表名，用于多表查询中避免与其它表的字段重名
+pub(crate) fn qualified_live_matcher<T: Table>(matcher: &Matcher) -> Matcher {
+    scoped_matcher::<T>(matcher, true)
+}
+
+fn scoped_matcher<T: Table>(matcher: &Matcher, qualified: bool) -> Matcher {
+    let mut matcher = matcher.clone();
+    if let Some(column) = T::soft_delete_column()
+        && !matcher.is_with_deleted()
+    {
+        let column = if qualified {
+            format!("{}.{}", T::table_name(), column)
+        } else {
+            column.to_string()
+        };
+        matcher.and(&column, Operator::IsNull, ());
+    }
+    matcher
+}
+
+// 语句执行出错，记录出错的语句
+pub(crate) fn sql_error(e: sqlite::Error, sql: &str) -> Error {
+    SqlError::from_sqlite(&e).with_sql(sql).into()
+}
+
 // 为 sqlite 实现专门接口
 impl Dao<Connection> {
     pub fn create_table(&self, sql: &str) -> Result<(), Error> {
-        match self.connect.execute(sql) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Error::SqlError(e.to_string())),
+        self.batch(sql)
+    }
+
+    // 删除满足条件的数据，使用软删除的表只写入删除时间
+    fn remove<T: Table>(&self, matcher: &Matcher) -> Result<usize, Error> {
+        if let Some(column) = T::soft_delete_column() {
+            let mut values = JsonObject::new();
+            values.set_i64(column, now());
+            return self.update_where::<T>(matcher, values);
+        }
+        let mut query = Query::new(&format!("delete from {}", T::table_name()));
+        query.append(where_clause(matcher));
+        self.execute(&query)
+    }
+
+    /// 最近一次成功插入的 rowid，同一个连接上有效
+    pub fn last_insert_rowid(&self) -> Result<i64, Error> {
+        let mut statement = self.prepare(&Query::new("SELECT last_insert_rowid() AS id"))?;
+        match statement.next() {
+            Ok(State::Row) => statement
+                .read::<i64, _>("id")
+                .map_err(Error::from),
+            Ok(State::Done) => Ok(0),
+            Err(e) => Err(Error::from(e)),
         }
     }
+
+    /// 执行任意查询语句，按顺序绑定参数，每一行以列名为 key 转为 JsonObject
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::sources::Dao;
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// let rows = dao.query_raw("select ? + 1 as n, ? as s", &[&1, &"a"]).unwrap();
+    /// assert_eq!(rows[0].get_i64("n"), Some(2));
+    /// assert_eq!(rows[0].get_str("s"), Some("a"));
+    /// ```
+    pub fn query_raw(&self, sql: &str, params: &[&dyn ToType]) -> Result<Vec<JsonObject>, Error> {
+        let query = Query::with_params(sql, params.iter().map(|p| p.to_type()).collect());
+        self.query_json(&query)
+    }
+
+    /// 执行不带参数的语句，可包含多条
+    pub(crate) fn batch(&self, sql: &str) -> Result<(), Error> {
+        self.observe(sql, 0, |_| 0, || {
+            self.connect.execute(sql).map_err(|e| sql_error(e, sql))
+        })
+    }
+
+    /// 执行查询语句，每一行转为 JsonObject
+    pub(crate) fn query_json(&self, query: &Query) -> Result<Vec<JsonObject>, Error> {
+        self.observe(&query.sql, query.params.len(), Vec::len, || {
+            let mut statement = self.prepare(query)?;
+            read_json_rows(&mut statement, &query.sql)
+        })
+    }
+
+    /// 执行查询语句，每一行转为 T 并调用 after_load
+    pub(crate) fn fetch<T: Table>(&self, query: &Query) -> Result<Vec<T>, Error> {
+        self.query_json(query)?
+            .iter()
+            .map(|row| {
+                let mut entity = T::from_json_object(row)?;
+                entity.after_load()?;
+                Ok(entity)
+            })
+            .collect()
+    }
+
+    /// 执行 f 并将语句、耗时、行数及错误通知 observer，rows 取得结果对应的行数
+    pub(crate) fn observe<R>(
+        &self,
+        sql: &str,
+        params: usize,
+        rows: impl Fn(&R) -> usize,
+        f: impl FnOnce() -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let start = Instant::now();
+        let result = f();
+        self.observer.on_query(&QueryEvent {
+            sql,
+            params,
+            duration: start.elapsed(),
+            rows: result.as_ref().map(rows).unwrap_or_default(),
+            error: result.as_ref().err(),
+        });
+        result
+    }
+
+    /// 预编译语句，并按顺序绑定参数
+    pub(crate) fn prepare(&self, query: &Query) -> Result<Statement<'_>, Error> {
+        let mut statement = match self.connect.prepare(&query.sql) {
+            Ok(statement) => statement,
+            Err(e) => return Err(sql_error(e, &query.sql)),
+        };
+        for (i, param) in query.params.iter().enumerate() {
+            if let Err(e) = statement.bind((i + 1, to_sqlite_value(param))) {
+                return Err(sql_error(e, &query.sql));
+            }
+        }
+        Ok(statement)
+    }
+
+    /// 执行增删改语句，返回受影响的行数
+    pub(crate) fn execute(&self, query: &Query) -> Result<usize, Error> {
+        self.observe(&query.sql, query.params.len(), |rows| *rows, || {
+            let mut statement = self.prepare(query)?;
+            loop {
+                match statement.next() {
+                    Ok(State::Row) => continue,
+                    Ok(State::Done) => break,
+                    Err(e) => return Err(sql_error(e, &query.sql)),
+                }
+            }
+            Ok(self.connect.change_count())
+        })
+    }
+}
+
+// 调用 before_insert 后转为 JsonObject，并写入创建时间与修改时间
+pub(crate) fn insert_object<T: Table>(mut entity: T, now: i64) -> Result<JsonObject, Error> {
+    entity.before_insert()?;
+    let mut object = entity.to_json_object()?;
+    if let Some(column) = T::created_at_column()
+        && object.get_i64(column).unwrap_or_default() == 0
+    {
+        object.remove(column);
+        object.set_i64(column, now);
+    }
+    stamp_update::<T>(&mut object, now);
+    Ok(object)
+}
+
+// 写入修改时间
+fn stamp_update<T: Table>(object: &mut JsonObject, now: i64) {
+    if let Some(column) = T::updated_at_column() {
+        object.remove(column);
+        object.set_i64(column, now);
+    }
+}
+
+// 需要插入的字段，跳过自增的主键与实体中没有的字段
+pub(crate) fn insert_columns<T: Table>(object: &JsonObject) -> Vec<&'static str> {
+    T::columns()
+        .into_iter()
+        .filter(|column| !(*column == T::id() && T::id_auto_increase()))
+        .filter(|column| object.get_data(column).is_some())
+        .collect()
+}
+
+// 生成插入语句并按字段顺序绑定参数，没有字段时使用默认值插入
+pub(crate) fn insert_query<T: Table>(columns: &[&str], object: &JsonObject) -> Query {
+    if columns.is_empty() {
+        return Query::new(&format!("insert into {} default values", T::table_name()));
+    }
+    let mut query = Query::new(&format!(
+        "insert into {} ({}) values ({})",
+        T::table_name(),
+        columns
+            .iter()
+            .map(|c| format!("`{}`", c))
+            .collect::<Vec<_>>()
+            .join(","),
+        vec!["?"; columns.len()].join(",")
+    ));
+    for column in columns {
+        if let Some(v) = object.get_data(column) {
+            query.bind(v);
+        }
+    }
+    query
+}
+
+// 检查字段是否都属于该表，字段名会拼接到 sql 中，不能来自未校验的输入
+fn check_columns<T: Table>(columns: &[&str]) -> Result<(), Error> {
+    let all = T::columns();
+    match columns.iter().find(|c| !all.contains(c)) {
+        Some(c) => Err(Error::ArgError(format!(
+            "column {} not found in {}",
+            c,
+            T::table_name()
+        ))),
+        None => Ok(()),
+    }
+}
+
+// 条件为空时返回空语句，否则返回 " where ..."
+pub(crate) fn where_clause(matcher: &Matcher) -> Query {
+    if matcher.is_empty() {
+        return Query::default();
+    }
+    let mut query = Query::new(" where ");
+    query.append(matcher.parse());
+    query
 }
 
-// 读取行数据，转换为目标类型的数组返回
-fn read_sqlite_row<T: Table>(statement: &mut Statement<'_>) -> Result<Vec<T>, Error> {
+// 取实体的主键值，优先使用 JsonObject 中的原始类型
+fn id_param<T: Table>(entity: &T, object: &JsonObject) -> Type {
+    match object.get_data(T::id()) {
+        Some(v) => v.clone(),
+        None => Type::String(entity.id_value()),
+    }
+}
+
+// 将 Type 转为 sqlite 可绑定的值，超出 i64 范围的整数以字符串保存
+fn to_sqlite_value(value: &Type) -> Value {
+    match value {
+        Type::I8(v) => Value::Integer(*v as i64),
+        Type::I16(v) => Value::Integer(*v as i64),
+        Type::I32(v) => Value::Integer(*v as i64),
+        Type::I64(v) => Value::Integer(*v),
+        Type::ISIZE(v) => Value::Integer(*v as i64),
+        Type::U8(v) => Value::Integer(*v as i64),
+        Type::U16(v) => Value::Integer(*v as i64),
+        Type::U32(v) => Value::Integer(*v as i64),
+        Type::I128(v) => i64::try_from(*v)
+            .map(Value::Integer)
+            .unwrap_or_else(|_| Value::String(v.to_string())),
+        Type::U64(v) => i64::try_from(*v)
+            .map(Value::Integer)
+            .unwrap_or_else(|_| Value::String(v.to_string())),
+        Type::U128(v) => i64::try_from(*v)
+            .map(Value::Integer)
+            .unwrap_or_else(|_| Value::String(v.to_string())),
+        Type::USIZE(v) => i64::try_from(*v)
+            .map(Value::Integer)
+            .unwrap_or_else(|_| Value::String(v.to_string())),
+        Type::F32(v) => Value::Float(*v as f64),
+        Type::F64(v) => Value::Float(*v),
+        Type::String(v) => Value::String(v.clone()),
+        Type::Boolean(v) => Value::Integer(*v as i64),
+        Type::JsonObject(v) => Value::String(v.to_json()),
+        Type::JsonList(v) => Value::String(v.to_json()),
+        Type::Null => Value::Null,
+    }
+}
+
+// 读取行数据，按列的实际类型转为 JsonObject，列名作为 key
+fn read_json_rows(statement: &mut Statement<'_>, sql: &str) -> Result<Vec<JsonObject>, Error> {
+    let error = |e| sql_error(e, sql);
     let mut list = vec![];
-    while let Ok(State::Row) = statement.next() {
+    loop {
+        match statement.next() {
+            Ok(State::Row) => {}
+            Ok(State::Done) => break,
+            Err(e) => return Err(error(e)),
+        }
         let mut param = JsonObject::new();
-        for column in statement.column_names().iter() {
-            let key: &str = &column;
-            let ctype = statement.column_type(key).unwrap();
+        for (index, key) in statement.column_names().iter().enumerate() {
+            let key: &str = key;
+            let ctype = statement.column_type(index).map_err(error)?;
             match ctype {
                 sqlite::Type::Float => {
-                    let value = statement.read::<f64, _>(key).unwrap();
+                    let value = statement.read::<f64, _>(index).map_err(error)?;
                     param.set_f64(key, value);
                 }
                 sqlite::Type::Integer => {
-                    let value = statement.read::<i64, _>(key).unwrap();
+                    let value = statement.read::<i64, _>(index).map_err(error)?;
                     param.set_i64(key, value);
                 }
                 sqlite::Type::String => {
-                    let value = statement.read::<String, _>(key).unwrap();
+                    let value = statement.read::<String, _>(index).map_err(error)?;
                     param.set_str(key, &value);
                 }
                 _ => {
@@ -179,10 +769,7 @@ fn read_sqlite_row<T: Table>(statement: &mut Statement<'_>) -> Result<Vec<T>, Er
                 }
             }
         }
-        match T::from_json_object(&param) {
-            Ok(v) => list.push(v),
-            Err(e) => return Err(e),
-        }
+        list.push(param);
     }
     Ok(list)
 }
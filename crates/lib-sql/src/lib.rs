@@ -2,9 +2,26 @@
 //!
 //! 一个用于数据库操作的 orm 库。支持 sqlite 和 mysql
 
+// 派生宏生成的代码通过 ::lib_sql 引用本库
+extern crate self as lib_sql;
+
+pub mod aggregate;
+pub mod async_dao;
+pub mod backup;
+pub mod cursor;
+pub mod filter;
+pub mod fts;
 pub mod interface;
+pub mod migrate;
+pub mod observer;
+pub mod pool;
+pub mod relation;
+pub mod schema;
 pub mod sources;
+pub mod testing;
 pub mod traits;
+pub mod transfer;
 pub mod utils;
+pub mod tests;
 
 pub use sqlite::Connection as SqliteConnection;
\ No newline at end of file
@@ -0,0 +1,266 @@
+use std::{fs, path::Path};
+
+use sqlite::Connection;
+
+use crate::{
+    sources::Dao,
+    traits::{CommInterface, Error, SqlError, Table, TransactionMode},
+    utils::now,
+};
+
+/// 一个版本的迁移，up 用于升级，down 用于回滚，均可包含多条 sql 语句
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub up: String,
+    pub down: String,
+}
+
+impl Migration {
+    pub fn new(version: i64, name: &str, up: &str, down: &str) -> Self {
+        Self {
+            version,
+            name: name.to_string(),
+            up: up.to_string(),
+            down: down.to_string(),
+        }
+    }
+}
+
+/// 迁移的执行状态，applied_at 为空表示尚未执行
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<i64>,
+}
+
+impl MigrationStatus {
+    pub fn is_applied(&self) -> bool {
+        self.applied_at.is_some()
+    }
+}
+
+/// 按版本号排序的迁移集合
+/// # Examples
+/// ```
+/// use lib_sql::migrate::{Migration, Migrator};
+///
+/// let migrator = Migrator::new(vec![
+///     Migration::new(1, "create_animals", "CREATE TABLE animals (id INTEGER PRIMARY KEY)", "DROP TABLE animals"),
+///     Migration::new(2, "add_name", "ALTER TABLE animals ADD COLUMN name TEXT", "ALTER TABLE animals DROP COLUMN name"),
+/// ]).unwrap();
+/// // dao.migrate(&migrator)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    /// 创建迁移集合，版本号不能重复
+    pub fn new(mut migrations: Vec<Migration>) -> Result<Self, Error> {
+        migrations.sort_by_key(|m| m.version);
+        for pair in migrations.windows(2) {
+            if pair[0].version == pair[1].version {
+                return Err(Error::ArgError(format!(
+                    "duplicate migration version {}",
+                    pair[0].version
+                )));
+            }
+        }
+        Ok(Self { migrations })
+    }
+
+    /// 从目录读取迁移，文件名格式为 `{version}_{name}.up.sql` 与 `{version}_{name}.down.sql`，
+    /// down 文件可以省略，省略后该版本不能回滚
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir)
+            .map_err(|e| Error::ConfigError(format!("path={:?}, e={:?}", dir, e)))?;
+
+        let mut migrations: Vec<Migration> = vec![];
+        for entry in entries {
+            let path = entry
+                .map_err(|e| Error::ConfigError(format!("path={:?}, e={:?}", dir, e)))?
+                .path();
+            let file_name = path.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+            let (stem, up) = if let Some(stem) = file_name.strip_suffix(".up.sql") {
+                (stem, true)
+            } else if let Some(stem) = file_name.strip_suffix(".down.sql") {
+                (stem, false)
+            } else {
+                continue;
+            };
+            let (version, name) = match stem.split_once('_') {
+                Some((version, name)) => (version, name),
+                None => (stem, ""),
+            };
+            let version: i64 = version.parse().map_err(|_| {
+                Error::ConfigError(format!("invalid migration file name {}", file_name))
+            })?;
+            let sql = fs::read_to_string(&path)
+                .map_err(|e| Error::ConfigError(format!("path={:?}, e={:?}", path, e)))?;
+
+            let index = match migrations.iter().position(|m| m.version == version) {
+                Some(index) => index,
+                None => {
+                    migrations.push(Migration::new(version, name, "", ""));
+                    migrations.len() - 1
+                }
+            };
+            if up {
+                migrations[index].up = sql;
+            } else {
+                migrations[index].down = sql;
+            }
+        }
+
+        if let Some(m) = migrations.iter().find(|m| m.up.is_empty()) {
+            return Err(Error::ConfigError(format!(
+                "migration {} has no up file",
+                m.version
+            )));
+        }
+        Migrator::new(migrations)
+    }
+
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// 最新的版本号，没有迁移时为 0
+    pub fn latest_version(&self) -> i64 {
+        self.migrations.last().map(|m| m.version).unwrap_or(0)
+    }
+}
+
+// schema_migrations 表，记录已执行的迁移
+#[derive(Debug, Clone, Table)]
+#[table(name = "schema_migrations")]
+struct AppliedMigration {
+    #[id]
+    version: i64,
+    name: String,
+    applied_at: i64,
+}
+
+// 为 sqlite 实现迁移
+impl Dao<Connection> {
+    /// 执行所有未执行的迁移，每个版本在单独的事务中执行，返回本次执行的版本号
+    pub fn migrate(&self, migrator: &Migrator) -> Result<Vec<i64>, Error> {
+        let applied = self.applied_migrations()?;
+        let mut versions = vec![];
+        for migration in migrator.migrations() {
+            if applied.iter().any(|m| m.version == migration.version) {
+                continue;
+            }
+            self.transaction_with(TransactionMode::Immediate, |tx| {
+                tx.batch(&migration.up)?;
+                tx.add(AppliedMigration {
+                    version: migration.version,
+                    name: migration.name.clone(),
+                    applied_at: now(),
+                })
+            })
+            .map_err(|e| migration_error(migration, "up", e))?;
+            versions.push(migration.version);
+        }
+        Ok(versions)
+    }
+
+    /// 回滚到指定版本，按版本号从大到小回滚所有大于该版本的迁移，返回本次回滚的版本号
+    pub fn rollback_to(&self, migrator: &Migrator, version: i64) -> Result<Vec<i64>, Error> {
+        let mut applied = self.applied_migrations()?;
+        applied.retain(|m| m.version > version);
+        applied.reverse();
+
+        let mut versions = vec![];
+        for record in applied {
+            let migration = migrator
+                .migrations()
+                .iter()
+                .find(|m| m.version == record.version);
+            let migration = match migration {
+                Some(m) if !m.down.trim().is_empty() => m,
+                _ => {
+                    return Err(Error::ArgError(format!(
+                        "migration {} {} can not be rolled back",
+                        record.version, record.name
+                    )));
+                }
+            };
+            self.transaction_with(TransactionMode::Immediate, |tx| {
+                tx.batch(&migration.down)?;
+                tx.delete(record.clone())
+            })
+            .map_err(|e| migration_error(migration, "down", e))?;
+            versions.push(record.version);
+        }
+        Ok(versions)
+    }
+
+    /// 所有迁移的执行状态，包含数据库中已执行但不在 migrator 中的版本
+    pub fn migration_status(&self, migrator: &Migrator) -> Result<Vec<MigrationStatus>, Error> {
+        let applied = self.applied_migrations()?;
+        let mut list: Vec<MigrationStatus> = migrator
+            .migrations()
+            .iter()
+            .map(|m| MigrationStatus {
+                version: m.version,
+                name: m.name.clone(),
+                applied_at: applied
+                    .iter()
+                    .find(|a| a.version == m.version)
+                    .map(|a| a.applied_at),
+            })
+            .collect();
+        for record in applied {
+            if !list.iter().any(|m| m.version == record.version) {
+                list.push(MigrationStatus {
+                    version: record.version,
+                    name: record.name,
+                    applied_at: Some(record.applied_at),
+                });
+            }
+        }
+        list.sort_by_key(|m| m.version);
+        Ok(list)
+    }
+
+    /// 当前数据库的版本号，即已执行的最大版本号，未执行过迁移时为 0
+    pub fn schema_version(&self) -> Result<i64, Error> {
+        let applied = self.applied_migrations()?;
+        Ok(applied.last().map(|m| m.version).unwrap_or(0))
+    }
+
+    // 读取已执行的迁移，按版本号排序
+    fn applied_migrations(&self) -> Result<Vec<AppliedMigration>, Error> {
+        self.batch(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
+            )",
+        )?;
+        let mut applied = self.list_all::<AppliedMigration>()?;
+        applied.sort_by_key(|m| m.version);
+        Ok(applied)
+    }
+}
+
+// 在错误信息前加上失败的迁移，保留语句出错时的分类与结果码
+fn migration_error(migration: &Migration, direction: &str, e: Error) -> Error {
+    let context = format!(
+        "migration {} {} {} failed",
+        migration.version, migration.name, direction
+    );
+    match e {
+        Error::SqlError(mut e) => {
+            e.message = format!("{}: {}", context, e.message);
+            Error::SqlError(e)
+        }
+        e => Error::SqlError(SqlError::new(&format!("{}: {}", context, e))),
+    }
+}
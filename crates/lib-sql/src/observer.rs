@@ -0,0 +1,117 @@
+use std::{sync::Arc, time::Duration};
+
+use lib_log::log::{self, LogLevel};
+use serde::Deserialize;
+
+use crate::traits::Error;
+
+/// 默认的慢查询阈值，单位毫秒
+pub const DEFAULT_SLOW_QUERY_MS: u64 = 200;
+
+/// 一次语句执行的信息。sql 只包含 `?` 占位符，不提供绑定的参数值，避免密码等敏感数据写入日志
+#[derive(Debug)]
+pub struct QueryEvent<'a> {
+    pub sql: &'a str,
+    /// 绑定的参数个数
+    pub params: usize,
+    pub duration: Duration,
+    /// 查询返回的行数，或增删改影响的行数
+    pub rows: usize,
+    pub error: Option<&'a Error>,
+}
+
+/// 语句执行的观察者，每条语句执行完成后调用，可用于日志、统计耗时等。
+/// 在执行语句的线程中同步调用，实现中不应做耗时的操作
+/// # Examples
+/// ```
+/// use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+/// use lib_sql::observer::{QueryEvent, QueryObserver};
+///
+/// #[derive(Default)]
+/// struct Counter(AtomicUsize);
+///
+/// impl QueryObserver for Counter {
+///     fn on_query(&self, _event: &QueryEvent) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let counter = Arc::new(Counter::default());
+/// // dao.set_observer(counter.clone());
+/// // let pool = Pool::new_with_observer(config, counter.clone())?;
+/// ```
+pub trait QueryObserver: Send + Sync {
+    fn on_query(&self, event: &QueryEvent);
+}
+
+/// 语句日志的级别，对应配置文件中 [query_log] 的 level
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryLogLevel {
+    /// 不输出每条语句，慢查询与出错的语句仍会输出
+    #[default]
+    Off,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// 语句日志配置，对应配置文件中的 [query_log]
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct QueryLogConfig {
+    /// 每条语句的日志级别，默认 off
+    pub level: Option<QueryLogLevel>,
+    /// 慢查询阈值，单位毫秒，耗时超过时输出警告，默认 200
+    pub slow_ms: Option<u64>,
+}
+
+/// 默认的观察者，通过 lib-log 输出语句日志，慢查询与出错的语句以 warn 级别输出
+#[derive(Debug, Clone)]
+pub struct LogObserver {
+    level: QueryLogLevel,
+    slow: Duration,
+}
+
+impl LogObserver {
+    pub fn new(level: QueryLogLevel, slow: Duration) -> Self {
+        Self { level, slow }
+    }
+
+    /// 按配置创建，未配置时使用默认值
+    pub fn from_config(config: Option<&QueryLogConfig>) -> Self {
+        let config = config.cloned().unwrap_or_default();
+        Self::new(
+            config.level.unwrap_or_default(),
+            Duration::from_millis(config.slow_ms.unwrap_or(DEFAULT_SLOW_QUERY_MS)),
+        )
+    }
+}
+
+impl QueryObserver for LogObserver {
+    fn on_query(&self, event: &QueryEvent) {
+        let info = format!(
+            "sql={};params={};rows={};cost={:?}",
+            event.sql, event.params, event.rows, event.duration
+        );
+        if let Some(e) = event.error {
+            log::log_warn(&format!("{};error={}", info, e));
+        } else if event.duration >= self.slow {
+            log::log_warn(&format!("slow;{}", info));
+        } else {
+            let level = match self.level {
+                QueryLogLevel::Off => return,
+                QueryLogLevel::Debug => LogLevel::Debug,
+                QueryLogLevel::Info => LogLevel::Info,
+                QueryLogLevel::Warn => LogLevel::Warn,
+                QueryLogLevel::Error => LogLevel::Error,
+            };
+            log::log(level, &info);
+        }
+    }
+}
+
+/// 按配置创建默认的观察者
+pub(crate) fn default_observer(config: Option<&QueryLogConfig>) -> Arc<dyn QueryObserver> {
+    Arc::new(LogObserver::from_config(config))
+}
@@ -0,0 +1,214 @@
+use std::{
+    ops::Deref,
+    process,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use sqlite::Connection;
+
+use crate::{
+    observer::{QueryObserver, default_observer},
+    sources::Dao,
+    traits::Error,
+    utils::{Config, MEMORY, read_config},
+};
+
+const DEFAULT_MAX_SIZE: usize = 8;
+const DEFAULT_TIMEOUT: u64 = 3000;
+
+/// sqlite 连接池，连接按需创建，最多同时存在 max_size 个。
+/// 克隆得到的连接池共享同一组连接，可直接放入 actix 的 web::Data 中
+/// # Examples
+/// ```no_run
+/// use lib_sql::pool::Pool;
+/// use lib_sql::traits::CommInterface;
+///
+/// let pool = Pool::new_with_path("./conf/config.toml").unwrap();
+/// let dao = pool.get().unwrap(); // 离开作用域后连接自动归还
+/// // dao.list_all::<User>();
+/// ```
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: Config,
+    max_size: usize,
+    timeout: Duration,
+    pragmas: Vec<String>,
+    observer: Arc<dyn QueryObserver>,
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+struct State {
+    idle: Vec<Dao<Connection>>,
+    // 已创建的连接数，包含正在使用的连接
+    size: usize,
+}
+
+impl Pool {
+    /// 使用配置创建连接池，会立即创建一个连接用于检查配置是否可用。
+    /// datasource 为 ":memory:" 时所有连接共享同一个内存数据库，连接池释放后数据丢失，用于测试
+    pub fn new(config: Config) -> Result<Self, Error> {
+        let observer = default_observer(config.query_log.as_ref());
+        Pool::new_with_observer(config, observer)
+    }
+
+    /// 同 new，所有连接使用指定的语句观察者
+    pub fn new_with_observer(
+        config: Config,
+        observer: Arc<dyn QueryObserver>,
+    ) -> Result<Self, Error> {
+        let config = shared_memory(config);
+        let pool_config = config.pool.clone().unwrap_or_default();
+        let max_size = pool_config.max_size.unwrap_or(DEFAULT_MAX_SIZE);
+        if max_size == 0 {
+            return Err(Error::ConfigError("pool.max_size must be greater than 0".to_string()));
+        }
+        let pool = Pool {
+            inner: Arc::new(Inner {
+                config,
+                max_size,
+                timeout: Duration::from_millis(pool_config.timeout.unwrap_or(DEFAULT_TIMEOUT)),
+                pragmas: pool_config.pragmas.unwrap_or_default(),
+                observer,
+                state: Mutex::new(State {
+                    idle: vec![],
+                    size: 0,
+                }),
+                available: Condvar::new(),
+            }),
+        };
+        let dao = pool.inner.connect()?;
+        let mut state = pool.inner.state.lock().unwrap();
+        state.idle.push(dao);
+        state.size = 1;
+        drop(state);
+        Ok(pool)
+    }
+
+    /// 读取指定路径的配置文件并创建连接池
+    pub fn new_with_path(config_path: &str) -> Result<Self, Error> {
+        Pool::new(read_config(config_path)?)
+    }
+
+    /// 取出一个连接，没有空闲连接且已达到最大连接数时等待，超时返回 PoolError
+    pub fn get(&self) -> Result<PooledDao, Error> {
+        let inner = &self.inner;
+        let deadline = Instant::now() + inner.timeout;
+        let mut state = inner.state.lock().unwrap();
+        loop {
+            if let Some(dao) = state.idle.pop() {
+                return Ok(PooledDao {
+                    dao: Some(dao),
+                    pool: inner.clone(),
+                });
+            }
+            if state.size < inner.max_size {
+                // 先占位再创建，创建连接时不持有锁
+                state.size += 1;
+                drop(state);
+                return match inner.connect() {
+                    Ok(dao) => Ok(PooledDao {
+                        dao: Some(dao),
+                        pool: inner.clone(),
+                    }),
+                    Err(e) => {
+                        inner.state.lock().unwrap().size -= 1;
+                        inner.available.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::PoolError(format!(
+                    "timed out after {:?} waiting for a connection, max_size={}",
+                    inner.timeout, inner.max_size
+                )));
+            }
+            state = inner.available.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// 连接池使用的配置
+    pub fn config(&self) -> &Config {
+        &self.inner.config
+    }
+
+    /// 最大连接数
+    pub fn max_size(&self) -> usize {
+        self.inner.max_size
+    }
+
+    /// 已创建的连接数
+    pub fn size(&self) -> usize {
+        self.inner.state.lock().unwrap().size
+    }
+
+    /// 空闲的连接数
+    pub fn idle(&self) -> usize {
+        self.inner.state.lock().unwrap().idle.len()
+    }
+}
+
+// 每个连接的 :memory: 是独立的数据库，连接池改为使用进程内共享的 memdb，
+// 所有连接访问同一个内存数据库，连接池的最后一个连接关闭后数据丢失
+fn shared_memory(config: Config) -> Config {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    if config.datasource != MEMORY {
+        return config;
+    }
+    let datasource = format!(
+        "file:/lib-sql-memory-{}-{}?vfs=memdb",
+        process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    );
+    Config {
+        datasource,
+        ..config
+    }
+}
+
+impl Inner {
+    // 创建新连接，并执行配置的 pragma
+    fn connect(&self) -> Result<Dao<Connection>, Error> {
+        let mut dao = Dao::open(self.config.clone())?;
+        dao.set_observer(self.observer.clone());
+        for pragma in &self.pragmas {
+            dao.connect
+                .execute(format!("PRAGMA {}", pragma))
+                .map_err(|e| Error::ConfigError(format!("pragma {} error:{}", pragma, e)))?;
+        }
+        Ok(dao)
+    }
+}
+
+/// 从连接池取出的连接，可当作 Dao 使用，drop 时归还连接池
+pub struct PooledDao {
+    dao: Option<Dao<Connection>>,
+    pool: Arc<Inner>,
+}
+
+impl Deref for PooledDao {
+    type Target = Dao<Connection>;
+
+    fn deref(&self) -> &Self::Target {
+        self.dao.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledDao {
+    fn drop(&mut self) {
+        if let Some(dao) = self.dao.take() {
+            self.pool.state.lock().unwrap().idle.push(dao);
+            self.pool.available.notify_one();
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use lib_json::types::Type;
+
+use crate::interface::qualified_live_matcher;
+use crate::traits::Table;
+use crate::utils::{Matcher, Operator, Query};
+
+// 多对多预加载时，结果中保存父实体主键的字段
+const RELATED_KEY: &str = "__related_key";
+
+/// 实体之间的关联，用于 load_related 批量预加载与 Matcher::and_related 按关联数据过滤。
+/// 以下说明中 P 为当前实体，C 为关联的实体
+/// # Examples
+/// ```
+/// use lib_sql::relation::Relation;
+/// use lib_sql::traits::Table;
+/// use lib_sql::utils::{Matcher, Operator, Search};
+///
+/// #[derive(Debug, Default, Table)]
+/// #[table(name = "users")]
+/// struct User {
+///     #[id(auto_increment)]
+///     id: i64,
+///     username: String,
+/// }
+///
+/// #[derive(Debug, Default, Table)]
+/// #[table(name = "roles")]
+/// struct Role {
+///     #[id(auto_increment)]
+///     id: i64,
+///     name: String,
+/// }
+///
+/// // 查询拥有 admin 角色的用户
+/// let roles = Relation::many_to_many("user_roles", "user_id", "role_id");
+/// let mut admin = Matcher::new();
+/// admin.and("name", Operator::Eq, "admin");
+/// let mut search = Search::new();
+/// search.with_related::<User, Role>(&roles, admin);
+/// assert_eq!(
+///     search.parse(User::table_name()).sql,
+///     "select * from users where ((exists (select 1 from user_roles \
+///      where user_roles.user_id = users.id and user_roles.role_id in \
+///      (select roles.id from roles where ((name = ?))))))"
+/// );
+/// // 批量读取每个用户的角色，只执行一次查询
+/// // let users = dao.list::<User>(&mut search)?;
+/// // let user_roles: Vec<Vec<Role>> = dao.load_related::<User, Role>(&users, &roles)?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Relation {
+    /// P 中的外键引用 C 的主键，如会话属于用户：sessions.user_id -> users.id
+    BelongsTo { foreign_key: String },
+    /// C 中的外键引用 P 的主键，如用户有多个会话：users.id <- sessions.user_id
+    HasMany { foreign_key: String },
+    /// 通过中间表关联，中间表的 local_key 引用 P 的主键，foreign_key 引用 C 的主键，
+    /// 如用户与角色：users.id <- user_roles.user_id，user_roles.role_id -> roles.id
+    ManyToMany {
+        join_table: String,
+        local_key: String,
+        foreign_key: String,
+    },
+}
+
+impl Relation {
+    pub fn belongs_to(foreign_key: &str) -> Self {
+        Relation::BelongsTo {
+            foreign_key: foreign_key.to_string(),
+        }
+    }
+
+    pub fn has_many(foreign_key: &str) -> Self {
+        Relation::HasMany {
+            foreign_key: foreign_key.to_string(),
+        }
+    }
+
+    pub fn many_to_many(join_table: &str, local_key: &str, foreign_key: &str) -> Self {
+        Relation::ManyToMany {
+            join_table: join_table.to_string(),
+            local_key: local_key.to_string(),
+            foreign_key: foreign_key.to_string(),
+        }
+    }
+
+    /// P 中用于关联的字段
+    pub(crate) fn parent_key<P: Table>(&self) -> &str {
+        match self {
+            Relation::BelongsTo { foreign_key } => foreign_key,
+            _ => P::id(),
+        }
+    }
+
+    /// 预加载结果中与 parent_key 对应的字段
+    pub(crate) fn child_key<C: Table>(&self) -> &str {
+        match self {
+            Relation::BelongsTo { .. } => C::id(),
+            Relation::HasMany { foreign_key } => foreign_key,
+            Relation::ManyToMany { .. } => RELATED_KEY,
+        }
+    }
+
+    /// 按父实体的关联字段值批量查询 C，排除已软删除的数据
+    pub(crate) fn load_query<C: Table>(&self, keys: Vec<Type>) -> Query {
+        let child = C::table_name();
+        let mut matcher = qualified_live_matcher::<C>(&Matcher::new());
+        let mut query = match self {
+            Relation::ManyToMany {
+                join_table,
+                local_key,
+                foreign_key,
+            } => {
+                matcher.and(&format!("{}.{}", join_table, local_key), Operator::In, keys);
+                Query::new(&format!(
+                    "select {child}.*,{join_table}.{local_key} as {RELATED_KEY} from {child} \
+                     join {join_table} on {join_table}.{foreign_key} = {child}.{}",
+                    C::id()
+                ))
+            }
+            _ => {
+                let key = format!("{}.{}", child, self.child_key::<C>());
+                matcher.and(&key, Operator::In, keys);
+                Query::new(&format!("select * from {}", child))
+            }
+        };
+        query
+            .push_sql(" where ")
+            .append(matcher.parse())
+            .push_sql(&format!(" order by {}.{}", child, C::id()));
+        query
+    }
+
+    /// 关联的 C 中存在满足条件的数据，生成 exists 子查询，C 的字段在子查询中可以直接使用
+    pub(crate) fn exists<P: Table, C: Table>(&self, matcher: &Matcher) -> Query {
+        let (parent, child) = (P::table_name(), C::table_name());
+        let cond = qualified_live_matcher::<C>(matcher).parse();
+        let mut query = match self {
+            Relation::BelongsTo { foreign_key } => Query::new(&format!(
+                "(exists (select 1 from {child} where {child}.{} = {parent}.{foreign_key}",
+                C::id()
+            )),
+            Relation::HasMany { foreign_key } => Query::new(&format!(
+                "(exists (select 1 from {child} where {child}.{foreign_key} = {parent}.{}",
+                P::id()
+            )),
+            Relation::ManyToMany {
+                join_table,
+                local_key,
+                foreign_key,
+            } => {
+                // 条件放在 C 的子查询中，避免与中间表的字段重名
+                let mut query = Query::new(&format!(
+                    "(exists (select 1 from {join_table} where {join_table}.{local_key} = {parent}.{} \
+                     and {join_table}.{foreign_key} in (select {child}.{} from {child}",
+                    P::id(),
+                    C::id()
+                ));
+                if !cond.is_empty() {
+                    query.push_sql(" where ").append(cond);
+                }
+                query.push_sql(")))");
+                return query;
+            }
+        };
+        if !cond.is_empty() {
+            query.push_sql(" and ").append(cond);
+        }
+        query.push_sql("))");
+        query
+    }
+}
@@ -0,0 +1,319 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+use sqlite::Connection;
+
+use crate::{
+    sources::Dao,
+    traits::{Column, Error, SqlType, Table},
+    utils::Query,
+};
+
+/// 根据 Table 的字段描述生成建表语句
+/// # Examples
+/// ```
+/// use lib_sql::schema::create_table_sql;
+/// use lib_sql::traits::Table;
+///
+/// #[derive(Table)]
+/// #[table(name = "users")]
+/// struct User {
+///     #[id(auto_increment)]
+///     id: i32,
+///     #[column(unique)]
+///     username: String,
+/// }
+///
+/// assert_eq!(
+///     create_table_sql::<User>(),
+///     "CREATE TABLE IF NOT EXISTS users (
+///     id INTEGER PRIMARY KEY AUTOINCREMENT,
+///     username TEXT NOT NULL UNIQUE
+/// )"
+/// );
+/// ```
+pub fn create_table_sql<T: Table>() -> String {
+    let columns: Vec<String> = T::column_defs()
+        .iter()
+        .map(|c| format!("    {}", c.to_sql()))
+        .collect();
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n{}\n)",
+        T::table_name(),
+        columns.join(",\n")
+    )
+}
+
+/// 根据 Table 的字段描述生成索引语句，索引名为 idx_{表名}_{字段名}
+pub fn create_index_sql<T: Table>() -> Vec<String> {
+    T::column_defs()
+        .iter()
+        .filter(|c| c.index && !c.primary_key)
+        .map(|c| {
+            format!(
+                "CREATE INDEX IF NOT EXISTS idx_{0}_{1} ON {0} ({1})",
+                T::table_name(),
+                c.name
+            )
+        })
+        .collect()
+}
+
+/// 启动时表结构检查的方式，对应配置文件中的 schema_check
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaCheckMode {
+    /// 不一致时拒绝启动
+    #[default]
+    Strict,
+    /// 不一致时只输出警告
+    Warn,
+    /// 不检查
+    Off,
+}
+
+/// 一个实体对应的表结构
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    pub name: &'static str,
+    pub id: &'static str,
+    pub columns: Vec<Column>,
+}
+
+impl TableSchema {
+    pub fn of<T: Table>() -> Self {
+        Self {
+            name: T::table_name(),
+            id: T::id(),
+            columns: T::column_defs(),
+        }
+    }
+}
+
+/// 需要检查的实体集合
+/// # Examples
+/// ```
+/// use lib_sql::schema::Schema;
+/// use lib_sql::traits::Table;
+///
+/// #[derive(Table)]
+/// #[table(name = "users")]
+/// struct User {
+///     #[id(auto_increment)]
+///     id: i32,
+///     username: String,
+/// }
+///
+/// let schema = Schema::new().register::<User>();
+/// assert_eq!(schema.tables().len(), 1);
+/// // let report = dao.check_schema(&schema)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    tables: Vec<TableSchema>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册一个实体，重复注册同名的表时只保留最后一次
+    pub fn register<T: Table>(mut self) -> Self {
+        self.tables.retain(|t| t.name != T::table_name());
+        self.tables.push(TableSchema::of::<T>());
+        self
+    }
+
+    pub fn tables(&self) -> &[TableSchema] {
+        &self.tables
+    }
+}
+
+/// 实体与数据库表结构的差异
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaIssue {
+    /// 表不存在
+    MissingTable { table: String },
+    /// 实体中的字段在表中不存在
+    MissingColumn { table: String, column: String },
+    /// 表中的字段在实体中不存在
+    ExtraColumn { table: String, column: String },
+    /// 字段类型不一致，actual 为建表时声明的类型
+    TypeMismatch {
+        table: String,
+        column: String,
+        expected: SqlType,
+        actual: String,
+    },
+    /// 主键不一致，actual 为表中的主键字段
+    PrimaryKeyMismatch {
+        table: String,
+        expected: String,
+        actual: Vec<String>,
+    },
+}
+
+impl Display for SchemaIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaIssue::MissingTable { table } => write!(f, "table {} does not exist", table),
+            SchemaIssue::MissingColumn { table, column } => {
+                write!(f, "column {}.{} does not exist", table, column)
+            }
+            SchemaIssue::ExtraColumn { table, column } => {
+                write!(f, "column {}.{} is not mapped by the entity", table, column)
+            }
+            SchemaIssue::TypeMismatch {
+                table,
+                column,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "column {}.{} expected {} but declared as {:?}",
+                table, column, expected, actual
+            ),
+            SchemaIssue::PrimaryKeyMismatch {
+                table,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "table {} expected primary key {} but found {:?}",
+                table, expected, actual
+            ),
+        }
+    }
+}
+
+/// 表结构检查的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaReport {
+    pub issues: Vec<SchemaIssue>,
+}
+
+impl SchemaReport {
+    /// 实体与数据库完全一致
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Display for SchemaReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let issues: Vec<String> = self.issues.iter().map(|i| i.to_string()).collect();
+        f.write_str(&issues.join("; "))
+    }
+}
+
+// PRAGMA table_info 的一行
+#[derive(Debug, Table)]
+#[table(name = "pragma_table_info")]
+struct ColumnInfo {
+    #[id]
+    cid: i64,
+    name: String,
+    #[column(rename = "type")]
+    decl_type: String,
+    pk: i64,
+}
+
+// 为 sqlite 实现表结构相关的接口
+impl Dao<Connection> {
+    /// 按 Table 的字段描述建表并创建索引，表已存在时不做处理
+    pub fn create_table_for<T: Table>(&self) -> Result<(), Error> {
+        self.batch(&create_table_sql::<T>())?;
+        for sql in create_index_sql::<T>() {
+            self.batch(&sql)?;
+        }
+        Ok(())
+    }
+
+    /// 将注册的实体与数据库中的表结构比较，返回所有差异
+    pub fn check_schema(&self, schema: &Schema) -> Result<SchemaReport, Error> {
+        let mut report = SchemaReport::default();
+        for table in schema.tables() {
+            self.check_table(table, &mut report.issues)?;
+        }
+        Ok(report)
+    }
+
+    fn check_table(&self, table: &TableSchema, issues: &mut Vec<SchemaIssue>) -> Result<(), Error> {
+        let mut query =
+            Query::new("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?");
+        query.bind(table.name);
+        if self.query_json(&query)?.is_empty() {
+            issues.push(SchemaIssue::MissingTable {
+                table: table.name.to_string(),
+            });
+            return Ok(());
+        }
+
+        let mut query = Query::new("SELECT cid, name, type, pk FROM pragma_table_info(?)");
+        query.bind(table.name);
+        let infos: Vec<ColumnInfo> = self.fetch(&query)?;
+
+        for column in &table.columns {
+            let Some(info) = infos
+                .iter()
+                .find(|i| i.name.eq_ignore_ascii_case(column.name))
+            else {
+                issues.push(SchemaIssue::MissingColumn {
+                    table: table.name.to_string(),
+                    column: column.name.to_string(),
+                });
+                continue;
+            };
+            if column.sql_type != SqlType::Any
+                && affinity(&column.sql_type.to_string()) != affinity(&info.decl_type)
+            {
+                issues.push(SchemaIssue::TypeMismatch {
+                    table: table.name.to_string(),
+                    column: column.name.to_string(),
+                    expected: column.sql_type,
+                    actual: info.decl_type.clone(),
+                });
+            }
+        }
+        for info in &infos {
+            if !table
+                .columns
+                .iter()
+                .any(|c| c.name.eq_ignore_ascii_case(&info.name))
+            {
+                issues.push(SchemaIssue::ExtraColumn {
+                    table: table.name.to_string(),
+                    column: info.name.clone(),
+                });
+            }
+        }
+
+        let mut keys: Vec<&ColumnInfo> = infos.iter().filter(|i| i.pk > 0).collect();
+        keys.sort_by_key(|i| i.pk);
+        if keys.len() != 1 || !keys[0].name.eq_ignore_ascii_case(table.id) {
+            issues.push(SchemaIssue::PrimaryKeyMismatch {
+                table: table.name.to_string(),
+                expected: table.id.to_string(),
+                actual: keys.iter().map(|i| i.name.clone()).collect(),
+            });
+        }
+        Ok(())
+    }
+}
+
+// 按 sqlite 的类型亲和性规则归类声明的类型，如 VARCHAR(20) 与 TEXT 视为一致
+fn affinity(decl: &str) -> SqlType {
+    let decl = decl.to_uppercase();
+    if decl.contains("INT") {
+        SqlType::Integer
+    } else if decl.contains("CHAR") || decl.contains("CLOB") || decl.contains("TEXT") {
+        SqlType::Text
+    } else if decl.contains("BLOB") || decl.trim().is_empty() {
+        SqlType::Blob
+    } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB") {
+        SqlType::Real
+    } else {
+        SqlType::Numeric
+    }
+}
@@ -1,10 +1,11 @@
-use std::{fmt::Debug, fs, path::Path};
+use std::{cell::Cell, fmt::Debug, fs, path::Path, sync::Arc};
 
-use sqlite::Connection;
+use sqlite::{Connection, OpenFlags};
 
 use crate::{
+    observer::{QueryObserver, default_observer},
     traits::{Connect, Error},
-    utils::{Config, read_config},
+    utils::{Config, MEMORY, read_config},
 };
 
 #[derive(Debug)]
@@ -18,6 +19,10 @@ pub enum Mode {
 pub struct Dao<T: Connect> {
     pub config: Config,
     pub connect: T,
+    // 当前事务的嵌套层数，0 表示不在事务中
+    pub(crate) depth: Cell<usize>,
+    // 语句执行的观察者
+    pub(crate) observer: Arc<dyn QueryObserver>,
 }
 
 // sqlite 的 dao
@@ -31,21 +36,56 @@ impl Dao<Connection> {
     /// 创建dao，使用指定的配置文件进行连接，配置文件不能为空，否则报错。配置文件为 toml 格式
     pub fn new_with_path(config_path: &str) -> Result<Self, Error> {
         // 读取配置文件，连接数据库
-        let config = read_config(config_path);
-        if let Err(e) = config {
-            return Err(e);
-        }
-        let config = config.unwrap();
+        Dao::open(read_config(config_path)?)
+    }
 
+    /// 按配置打开数据库连接，datasource 可以是：
+    /// - 文件路径，所在的目录不存在时创建
+    /// - ":memory:"，每个连接独立的内存数据库，连接关闭后数据丢失，见 Dao::memory
+    /// - sqlite 的 URI 文件名，如 "file:/test?vfs=memdb"
+    /// # Examples
+    /// ```
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::utils::Config;
+    ///
+    /// let dao = Dao::open(Config::memory()).unwrap();
+    /// dao.create_table("CREATE TABLE animals (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+    /// ```
+    pub fn open(config: Config) -> Result<Self, Error> {
         let datasource = &config.datasource;
 
         // 创建数据库的文件夹
-        let dir_path = Path::new(datasource).parent().unwrap();
-        if!dir_path.exists() {
-            fs::create_dir_all(dir_path).unwrap();
+        if datasource != MEMORY
+            && !datasource.starts_with("file:")
+            && let Some(dir) = Path::new(datasource)
+                .parent()
+                .filter(|d| !d.as_os_str().is_empty())
+            && !dir.exists()
+        {
+            fs::create_dir_all(dir).map_err(|e| {
+                Error::ConfigError(format!("create dir {} error:{}", dir.display(), e))
+            })?;
         }
 
-        let connect = sqlite::open(datasource).unwrap();
-        Ok(Dao { config, connect })
+        let flags = OpenFlags::new().with_create().with_read_write().with_uri();
+        let connect = Connection::open_with_flags(datasource, flags)
+            .map_err(|e| Error::ConfigError(format!("open {} error:{}", datasource, e)))?;
+        let observer = default_observer(config.query_log.as_ref());
+        Ok(Dao {
+            config,
+            connect,
+            depth: Cell::new(0),
+            observer,
+        })
+    }
+
+    /// 打开一个新的内存数据库，用于测试，连接关闭后数据丢失
+    pub fn memory() -> Result<Self, Error> {
+        Dao::open(Config::memory())
+    }
+
+    /// 替换语句执行的观察者，默认按配置中的 [query_log] 输出日志
+    pub fn set_observer(&mut self, observer: Arc<dyn QueryObserver>) {
+        self.observer = observer;
     }
 }
@@ -0,0 +1,154 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use sqlite::Connection;
+
+use crate::{
+    pool::Pool,
+    sources::Dao,
+    traits::{CommInterface, Error},
+    transfer::{Format, parse_records},
+    utils::{Config, Query},
+};
+
+/// 临时目录下的数据库文件，drop 时删除，用于需要多个连接或真实文件的测试。
+/// 内存数据库见 Dao::memory，连接池使用 ":memory:" 时所有连接共享同一个内存数据库
+/// # Examples
+/// ```
+/// use lib_sql::testing::TempDatabase;
+/// use lib_sql::traits::{CommInterface, Table};
+/// use lib_sql::transfer::Format;
+///
+/// #[derive(Debug, Default, Table)]
+/// #[table(name = "animals")]
+/// struct Animal {
+///     #[id(auto_increment)]
+///     id: i64,
+///     name: String,
+/// }
+///
+/// let db = TempDatabase::new();
+/// let pool = db.pool().unwrap();
+/// let dao = pool.get().unwrap();
+/// dao.create_table_for::<Animal>().unwrap();
+/// dao.load_fixture("animals", r#"[{"id":1,"name":"cat"}]"#, Format::Json)
+///     .unwrap();
+/// // 其它连接读取到同样的数据
+/// let other = db.dao().unwrap();
+/// assert_eq!(other.list_all::<Animal>().unwrap()[0].name, "cat");
+/// ```
+pub struct TempDatabase {
+    path: PathBuf,
+}
+
+impl TempDatabase {
+    /// 在系统临时目录下生成不重复的文件名，文件在第一次打开连接时创建
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "lib-sql-{}-{}.db",
+            process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 使用该文件的配置，其它均为默认值，可以修改后再创建 Dao 或 Pool
+    pub fn config(&self) -> Config {
+        Config::new(&self.path.to_string_lossy())
+    }
+
+    pub fn dao(&self) -> Result<Dao<Connection>, Error> {
+        Dao::open(self.config())
+    }
+
+    pub fn pool(&self) -> Result<Pool, Error> {
+        Pool::new(self.config())
+    }
+}
+
+impl Default for TempDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TempDatabase {
+    // 同时删除 sqlite 的日志文件，连接应在此之前关闭
+    fn drop(&mut self) {
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            let mut path = self.path.as_os_str().to_owned();
+            path.push(suffix);
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// 为 sqlite 实现加载测试数据的接口
+impl Dao<Connection> {
+    /// 将测试数据按字段名原样写入 table，不经过实体的转换与钩子，不检查字段类型，
+    /// 可以写入任意字段，如创建时间与软删除时间。在一个事务中写入，返回写入的条数
+    pub fn load_fixture(&self, table: &str, data: &str, format: Format) -> Result<usize, Error> {
+        let records = parse_records(data, format)?;
+        self.transaction(|tx| {
+            for (i, record) in records.iter().enumerate() {
+                let record = record
+                    .as_ref()
+                    .map_err(|e| Error::ArgError(format!("{} row {}: {}", table, i + 1, e)))?;
+                let columns = record.keys();
+                let mut query = Query::new(&format!(
+                    "insert into `{}` ({}) values ({})",
+                    table,
+                    columns
+                        .iter()
+                        .map(|c| format!("`{}`", c))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    vec!["?"; columns.len()].join(",")
+                ));
+                for column in &columns {
+                    if let Some(v) = record.get_data(column) {
+                        query.bind(v);
+                    }
+                }
+                tx.execute(&query)?;
+            }
+            Ok(records.len())
+        })
+    }
+
+    /// 按文件名顺序加载目录下的测试数据，文件名为表名，扩展名为格式，
+    /// 如 fixtures/01_users.json 写入 users 表（文件名中 _ 之前为数字时作为排序用的前缀）。
+    /// 返回写入的总条数
+    pub fn load_fixtures(&self, dir: impl AsRef<Path>) -> Result<usize, Error> {
+        let dir = dir.as_ref();
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(|e| Error::ConfigError(format!("read dir {} error:{}", dir.display(), e)))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && Format::from_path(p).is_some())
+            .collect();
+        files.sort();
+
+        let mut count = 0;
+        for path in files {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let table = match stem.split_once('_') {
+                Some((n, table)) if n.chars().all(|c| c.is_ascii_digit()) => table,
+                _ => &stem,
+            };
+            let data = fs::read_to_string(&path)
+                .map_err(|e| Error::ConfigError(format!("read {} error:{}", path.display(), e)))?;
+            let format = Format::from_path(&path).unwrap_or(Format::Json);
+            count += self.load_fixture(table, &data, format)?;
+        }
+        Ok(count)
+    }
+}
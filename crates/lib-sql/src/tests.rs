@@ -0,0 +1,1811 @@
+#![cfg(test)]
+
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake},
+    thread::{self, Thread},
+};
+
+use lib_json::object::JsonObject;
+use sqlite::Connection;
+
+use crate::{
+    aggregate::Aggregate,
+    async_dao::AsyncDao,
+    backup::BackupConfig,
+    filter::{FieldFilter, FilterValue, SearchSpec},
+    fts::{TextSearch, quote},
+    migrate::{Migration, Migrator},
+    observer::{QueryEvent, QueryObserver},
+    pool::Pool,
+    relation::Relation,
+    schema::{Schema, SchemaIssue, create_index_sql, create_table_sql},
+    sources::Dao,
+    testing::TempDatabase,
+    traits::{
+        Column, CommInterface, Error, Hooks, SqlErrorKind, SqlType, Table, TransactionMode,
+    },
+    transfer::{Format, ImportOptions},
+    utils::{Condition, Config, Matcher, Operator, PoolConfig, Search},
+};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Animal {
+    id: i64,
+    name: String,
+    age: i32,
+}
+
+impl Table for Animal {
+    fn id() -> &'static str {
+        "id"
+    }
+
+    fn id_auto_increase() -> bool {
+        true
+    }
+
+    fn columns() -> Vec<&'static str> {
+        vec!["id", "name", "age"]
+    }
+
+    fn id_value(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn table_name() -> &'static str {
+        "animals"
+    }
+
+    fn from_json_object(row: &JsonObject) -> Result<Self, Error> {
+        Ok(Animal {
+            id: row.get_i64("id").ok_or(Error::ParseError)?,
+            name: row.get_str("name").ok_or(Error::ParseError)?.to_string(),
+            age: row.get_i32("age").ok_or(Error::ParseError)?,
+        })
+    }
+
+    fn to_json_object(&self) -> Result<JsonObject, Error> {
+        let mut obj = JsonObject::new();
+        obj.set_i64("id", self.id);
+        obj.set_str("name", &self.name);
+        obj.set_i32("age", self.age);
+        Ok(obj)
+    }
+}
+
+const ANIMALS_SQL: &str = "CREATE TABLE animals (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, age INTEGER NOT NULL)";
+
+fn memory_dao() -> Dao<Connection> {
+    let dao = Dao::memory().unwrap();
+    dao.create_table(ANIMALS_SQL).unwrap();
+    dao
+}
+
+fn animal(name: &str, age: i32) -> Animal {
+    Animal {
+        id: 0,
+        name: name.to_string(),
+        age,
+    }
+}
+
+#[test]
+fn test_search_parse_binds_values() {
+    let mut search = Search::default();
+    search.matcher.and("name", Operator::Eq, "it's");
+    search.matcher.and("id", Operator::In, vec![1, 2, 3]);
+    search.matcher.or("name", Operator::Like, "cat");
+    let query = search.parse("animals");
+    assert_eq!(
+        query.sql,
+        "select * from animals where ((name = ?) and (id in (?,?,?))) and ((name like ?))"
+    );
+    assert_eq!(query.params.len(), 5);
+}
+
+#[test]
+fn test_quoted_values_round_trip() {
+    let dao = memory_dao();
+    let name = "o'brien\"; drop table animals; --";
+    assert_eq!(dao.add(animal(name, 3)).unwrap(), 1);
+    dao.add(animal("cat", 2)).unwrap();
+
+    let mut search = Search::default();
+    search.matcher.and("name", Operator::Eq, name);
+    let list = dao.list::<Animal>(&mut search).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].name, name);
+
+    let mut updated = list[0].clone();
+    updated.name = "it's updated".to_string();
+    assert_eq!(dao.set(updated.clone()).unwrap(), 1);
+
+    let mut search = Search::default();
+    search.matcher.and_str("name", Operator::Eq, "it's updated");
+    search.start = 0;
+    search.limit = 10;
+    let list = dao.list::<Animal>(&mut search).unwrap();
+    assert_eq!(search.total, 1);
+    assert_eq!(list, vec![updated.clone()]);
+
+    assert_eq!(dao.delete(updated).unwrap(), 1);
+    assert_eq!(dao.list_all::<Animal>().unwrap().len(), 1);
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Table)]
+#[table(name = "pets")]
+struct Pet {
+    #[id(auto_increment)]
+    pid: i64,
+    #[column(rename = "pet_name")]
+    name: String,
+    weight: f64,
+    legs: u32,
+    vaccinated: bool,
+    owner: Option<String>,
+    #[column(skip)]
+    note: String,
+}
+
+#[test]
+fn test_derive_table() {
+    assert_eq!(Pet::table_name(), "pets");
+    assert_eq!(Pet::id(), "pid");
+    assert!(Pet::id_auto_increase());
+    assert_eq!(
+        Pet::columns(),
+        vec!["pid", "pet_name", "weight", "legs", "vaccinated", "owner"]
+    );
+
+    let dao = memory_dao();
+    dao.create_table_for::<Pet>().unwrap();
+    let pet = Pet {
+        pid: 0,
+        name: "tom".to_string(),
+        weight: 4.5,
+        legs: 4,
+        vaccinated: true,
+        owner: None,
+        note: "ignored".to_string(),
+    };
+    dao.add(pet.clone()).unwrap();
+    let mut loaded = dao.list_all::<Pet>().unwrap().pop().unwrap();
+    assert_eq!(loaded.pid, 1);
+    assert_eq!(loaded.name, "tom");
+    assert_eq!(loaded.weight, 4.5);
+    assert_eq!(loaded.legs, 4);
+    assert!(loaded.vaccinated);
+    assert_eq!(loaded.owner, None);
+    assert_eq!(loaded.note, "");
+
+    loaded.owner = Some("jerry".to_string());
+    dao.set(loaded.clone()).unwrap();
+    assert_eq!(dao.list_all::<Pet>().unwrap(), vec![loaded]);
+}
+
+#[test]
+fn test_pool_reuses_connections() {
+    let config = Config {
+        datasource: ":memory:".to_string(),
+        pool: Some(PoolConfig {
+            max_size: Some(1),
+            timeout: Some(20),
+            pragmas: Some(vec!["foreign_keys = ON".to_string()]),
+        }),
+        ..Default::default()
+    };
+    let pool = Pool::new(config).unwrap();
+    let dao = pool.get().unwrap();
+    dao.create_table("CREATE TABLE animals (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")
+        .unwrap();
+    assert_eq!(pool.idle(), 0);
+
+    // 唯一的连接被占用，等待超时
+    assert!(matches!(pool.get(), Err(Error::PoolError(_))));
+    drop(dao);
+    assert_eq!(pool.idle(), 1);
+
+    // 归还后取到的是同一个内存库连接
+    let dao = pool.get().unwrap();
+    dao.add(animal("cat", 2)).unwrap();
+    assert_eq!(dao.list_all::<Animal>().unwrap().len(), 1);
+    assert_eq!(pool.size(), 1);
+}
+
+#[test]
+fn test_transaction_commit_and_rollback() {
+    let dao = memory_dao();
+    let count = dao
+        .transaction(|tx| {
+            tx.add(animal("cat", 1))?;
+            tx.add(animal("dog", 2))
+        })
+        .unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(dao.list_all::<Animal>().unwrap().len(), 2);
+
+    let result: Result<(), Error> = dao.transaction(|tx| {
+        tx.add(animal("bird", 3))?;
+        Err(Error::ArgError("abort".to_string()))
+    });
+    assert!(result.is_err());
+    assert_eq!(dao.list_all::<Animal>().unwrap().len(), 2);
+}
+
+#[test]
+fn test_transaction_rollback_on_panic() {
+    let dao = memory_dao();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = dao.transaction_with(TransactionMode::Immediate, |tx| {
+            tx.add(animal("cat", 1))?;
+            panic!("boom");
+            #[allow(unreachable_code)]
+            Ok(())
+        });
+    }));
+    assert!(result.is_err());
+    assert!(dao.list_all::<Animal>().unwrap().is_empty());
+
+    // 回滚后可以正常开启新的事务
+    dao.transaction(|tx| tx.add(animal("dog", 2))).unwrap();
+    assert_eq!(dao.list_all::<Animal>().unwrap().len(), 1);
+}
+
+#[test]
+fn test_nested_transaction_savepoint() {
+    let dao = memory_dao();
+    dao.transaction(|tx| {
+        tx.add(animal("cat", 1))?;
+        // 内层失败只回滚内层
+        let inner: Result<(), Error> = tx.transaction(|tx| {
+            tx.add(animal("dog", 2))?;
+            Err(Error::ArgError("inner".to_string()))
+        });
+        assert!(inner.is_err());
+        tx.transaction(|tx| tx.add(animal("bird", 3)))?;
+        Ok(())
+    })
+    .unwrap();
+    let names: Vec<String> = dao
+        .list_all::<Animal>()
+        .unwrap()
+        .into_iter()
+        .map(|a| a.name)
+        .collect();
+    assert_eq!(names, vec!["cat", "bird"]);
+}
+
+fn animal_migrator() -> Migrator {
+    Migrator::new(vec![
+        Migration::new(
+            2,
+            "add_owner",
+            "ALTER TABLE zoo ADD COLUMN owner TEXT",
+            "ALTER TABLE zoo DROP COLUMN owner",
+        ),
+        Migration::new(1, "create_zoo", "CREATE TABLE zoo (id INTEGER PRIMARY KEY)", "DROP TABLE zoo"),
+    ])
+    .unwrap()
+}
+
+#[test]
+fn test_migrate_and_rollback() {
+    let dao = memory_dao();
+    let migrator = animal_migrator();
+    assert_eq!(dao.schema_version().unwrap(), 0);
+    assert!(dao.migration_status(&migrator).unwrap().iter().all(|m| !m.is_applied()));
+
+    assert_eq!(dao.migrate(&migrator).unwrap(), vec![1, 2]);
+    assert_eq!(dao.migrate(&migrator).unwrap(), Vec::<i64>::new());
+    assert_eq!(dao.schema_version().unwrap(), 2);
+    dao.batch("INSERT INTO zoo (id, owner) VALUES (1, 'tom')").unwrap();
+
+    assert_eq!(dao.rollback_to(&migrator, 1).unwrap(), vec![2]);
+    let status = dao.migration_status(&migrator).unwrap();
+    assert!(status[0].is_applied());
+    assert!(!status[1].is_applied());
+    assert!(dao.batch("SELECT owner FROM zoo").is_err());
+
+    assert_eq!(dao.rollback_to(&migrator, 0).unwrap(), vec![1]);
+    assert_eq!(dao.schema_version().unwrap(), 0);
+}
+
+#[test]
+fn test_failed_migration_is_rolled_back() {
+    let dao = memory_dao();
+    let migrator = Migrator::new(vec![
+        Migration::new(1, "create_zoo", "CREATE TABLE zoo (id INTEGER PRIMARY KEY)", ""),
+        Migration::new(2, "broken", "CREATE TABLE cage (id INTEGER); SELECT * FROM missing", ""),
+    ])
+    .unwrap();
+    assert!(dao.migrate(&migrator).is_err());
+    assert_eq!(dao.schema_version().unwrap(), 1);
+    assert!(dao.batch("SELECT * FROM cage").is_err());
+    // 没有 down 的版本不能回滚
+    assert!(dao.rollback_to(&migrator, 0).is_err());
+    assert!(Migrator::new(vec![Migration::new(1, "a", "", ""), Migration::new(1, "b", "", "")]).is_err());
+}
+
+#[test]
+fn test_migrator_from_dir() {
+    let dir = std::env::temp_dir().join(format!("lib_sql_migrations_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("0002_add_owner.up.sql"), "ALTER TABLE zoo ADD COLUMN owner TEXT").unwrap();
+    std::fs::write(dir.join("0001_create_zoo.up.sql"), "CREATE TABLE zoo (id INTEGER PRIMARY KEY)").unwrap();
+    std::fs::write(dir.join("0001_create_zoo.down.sql"), "DROP TABLE zoo").unwrap();
+    std::fs::write(dir.join("README.md"), "ignored").unwrap();
+
+    let migrator = Migrator::from_dir(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let migrations = migrator.migrations();
+    assert_eq!(migrations.len(), 2);
+    assert_eq!(migrations[0].name, "create_zoo");
+    assert_eq!(migrations[0].down, "DROP TABLE zoo");
+    assert_eq!(migrations[1].version, 2);
+    assert_eq!(migrator.latest_version(), 2);
+}
+
+#[derive(Debug, Clone, Table)]
+struct UserAccount {
+    id: String,
+    #[column(unique, filterable, sortable)]
+    username: String,
+    #[column(index, default = "''")]
+    email: String,
+    #[column(sql_type = "NUMERIC", default = "0", filterable, sortable)]
+    balance: f64,
+    nickname: Option<String>,
+}
+
+#[test]
+fn test_create_table_sql() {
+    assert_eq!(
+        create_table_sql::<Pet>(),
+        "CREATE TABLE IF NOT EXISTS pets (
+    pid INTEGER PRIMARY KEY AUTOINCREMENT,
+    pet_name TEXT NOT NULL,
+    weight REAL NOT NULL,
+    legs INTEGER NOT NULL,
+    vaccinated INTEGER NOT NULL,
+    owner TEXT
+)"
+    );
+
+    assert_eq!(UserAccount::table_name(), "user_account");
+    assert_eq!(
+        create_table_sql::<UserAccount>(),
+        "CREATE TABLE IF NOT EXISTS user_account (
+    id TEXT NOT NULL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL DEFAULT '',
+    balance NUMERIC NOT NULL DEFAULT 0,
+    nickname TEXT
+)"
+    );
+    assert_eq!(
+        create_index_sql::<UserAccount>(),
+        vec!["CREATE INDEX IF NOT EXISTS idx_user_account_email ON user_account (email)"]
+    );
+
+    // 手写的 Table 使用默认的字段描述
+    assert_eq!(
+        Animal::column_defs(),
+        vec![
+            Column::new("id", SqlType::Integer).primary_key().auto_increment(),
+            Column::new("name", SqlType::Any),
+            Column::new("age", SqlType::Any),
+        ]
+    );
+
+    let dao = memory_dao();
+    dao.create_table_for::<UserAccount>().unwrap();
+    dao.create_table_for::<UserAccount>().unwrap();
+    let account = UserAccount {
+        id: "u1".to_string(),
+        username: "tom".to_string(),
+        email: "tom@example.com".to_string(),
+        balance: 1.5,
+        nickname: None,
+    };
+    dao.add(account.clone()).unwrap();
+    // 唯一约束生效
+    let mut duplicate = account;
+    duplicate.id = "u2".to_string();
+    assert!(dao.add(duplicate).is_err());
+}
+
+#[test]
+fn test_check_schema() {
+    let dao = memory_dao();
+    dao.create_table_for::<Pet>().unwrap();
+    let schema = Schema::new().register::<Animal>().register::<Pet>();
+    assert!(dao.check_schema(&schema).unwrap().is_ok());
+
+    // 声明为 VARCHAR 的字段与 TEXT 亲和性一致
+    dao.create_table(
+        "CREATE TABLE user_account (
+            id VARCHAR(32) PRIMARY KEY,
+            username VARCHAR(64),
+            email TEXT,
+            balance INTEGER,
+            remark TEXT
+        )",
+    )
+    .unwrap();
+    let schema = Schema::new().register::<UserAccount>().register::<Pet>();
+    dao.batch("DROP TABLE pets").unwrap();
+    let report = dao.check_schema(&schema).unwrap();
+    assert_eq!(
+        report.issues,
+        vec![
+            SchemaIssue::TypeMismatch {
+                table: "user_account".to_string(),
+                column: "balance".to_string(),
+                expected: SqlType::Numeric,
+                actual: "INTEGER".to_string(),
+            },
+            SchemaIssue::MissingColumn {
+                table: "user_account".to_string(),
+                column: "nickname".to_string(),
+            },
+            SchemaIssue::ExtraColumn {
+                table: "user_account".to_string(),
+                column: "remark".to_string(),
+            },
+            SchemaIssue::MissingTable {
+                table: "pets".to_string(),
+            },
+        ]
+    );
+
+    dao.create_table("CREATE TABLE pets (pid INTEGER, pet_name TEXT, weight REAL, legs INTEGER, vaccinated INTEGER, owner TEXT)")
+        .unwrap();
+    let report = dao.check_schema(&Schema::new().register::<Pet>()).unwrap();
+    assert_eq!(
+        report.issues,
+        vec![SchemaIssue::PrimaryKeyMismatch {
+            table: "pets".to_string(),
+            expected: "pid".to_string(),
+            actual: vec![],
+        }]
+    );
+}
+
+#[test]
+fn test_insert_returns_generated_id() {
+    let dao = memory_dao();
+    assert_eq!(dao.insert(animal("cat", 1)).unwrap(), 1);
+    assert_eq!(dao.insert(animal("dog", 2)).unwrap(), 2);
+
+    let reloaded = dao.insert_and_reload(animal("fox", 3)).unwrap();
+    assert_eq!(reloaded, Animal { id: 3, name: "fox".to_string(), age: 3 });
+
+    let ids = dao
+        .add_many(vec![animal("owl", 4), animal("bee", 5), animal("ant", 6)])
+        .unwrap();
+    assert_eq!(ids, vec![4, 5, 6]);
+    assert!(dao.add_many(Vec::<Animal>::new()).unwrap().is_empty());
+
+    // 任意一行失败时整批回滚
+    dao.create_table("CREATE UNIQUE INDEX idx_animals_name ON animals (name)")
+        .unwrap();
+    assert!(dao.add_many(vec![animal("elk", 7), animal("cat", 8)]).is_err());
+    assert_eq!(dao.list_all::<Animal>().unwrap().len(), 6);
+}
+
+#[derive(Debug, Default, Table)]
+#[table(name = "tickets")]
+struct Ticket {
+    #[id(auto_increment)]
+    id: i64,
+}
+
+#[test]
+fn test_add_without_columns() {
+    // 只有自增主键时 add 与 insert 一样使用默认值写入
+    let dao = memory_dao();
+    dao.create_table_for::<Ticket>().unwrap();
+    assert_eq!(dao.add(Ticket::default()).unwrap(), 1);
+    assert_eq!(dao.insert(Ticket::default()).unwrap(), 2);
+    assert_eq!(dao.add_many(vec![Ticket::default()]).unwrap(), vec![3]);
+}
+
+#[test]
+fn test_keyed_lookups() {
+    let dao = memory_dao();
+    dao.add_many(vec![animal("cat", 1), animal("dog", 2), animal("fox", 2)])
+        .unwrap();
+
+    assert_eq!(dao.get::<Animal, _>(2).unwrap().unwrap().name, "dog");
+    assert!(dao.get::<Animal, _>(9).unwrap().is_none());
+
+    let mut matcher = Matcher::new();
+    matcher.and("age", Operator::Eq, 2);
+    assert_eq!(dao.count::<Animal>(&matcher).unwrap(), 2);
+    assert_eq!(dao.count::<Animal>(&Matcher::new()).unwrap(), 3);
+    assert!(dao.exists::<Animal>(&matcher).unwrap());
+
+    let mut matcher = Matcher::new();
+    matcher.and("name", Operator::Eq, "fox");
+    assert_eq!(dao.find_one::<Animal>(&matcher).unwrap().unwrap().id, 3);
+    assert_eq!(dao.delete_where::<Animal>(&matcher).unwrap(), 1);
+    assert!(!dao.exists::<Animal>(&matcher).unwrap());
+    assert!(dao.find_one::<Animal>(&matcher).unwrap().is_none());
+
+    // 空条件不允许删除
+    assert!(matches!(
+        dao.delete_where::<Animal>(&Matcher::new()),
+        Err(Error::ArgError(_))
+    ));
+    assert_eq!(dao.delete_by_id::<Animal, _>(1).unwrap(), 1);
+    assert_eq!(dao.count::<Animal>(&Matcher::new()).unwrap(), 1);
+}
+
+#[derive(Debug, Clone, PartialEq, Table)]
+#[table(name = "notes")]
+struct Note {
+    #[id(auto_increment)]
+    id: i64,
+    title: String,
+    #[column(soft_delete)]
+    deleted_at: Option<i64>,
+}
+
+fn note(title: &str) -> Note {
+    Note {
+        id: 0,
+        title: title.to_string(),
+        deleted_at: None,
+    }
+}
+
+#[test]
+fn test_soft_delete() {
+    let dao = memory_dao();
+    dao.create_table_for::<Note>().unwrap();
+    dao.add_many(vec![note("a"), note("b"), note("c")]).unwrap();
+    assert_eq!(Note::soft_delete_column(), Some("deleted_at"));
+
+    // delete 只写入删除时间，查询时排除
+    let a = dao.get::<Note, _>(1).unwrap().unwrap();
+    assert_eq!(dao.delete(a).unwrap(), 1);
+    assert_eq!(dao.delete_by_id::<Note, _>(1).unwrap(), 0);
+    let mut matcher = Matcher::new();
+    matcher.and("title", Operator::Eq, "b");
+    assert_eq!(dao.delete_where::<Note>(&matcher).unwrap(), 1);
+    assert_eq!(dao.get::<Note, _>(1).unwrap(), None);
+    assert_eq!(dao.list_all::<Note>().unwrap().len(), 1);
+    assert_eq!(dao.count::<Note>(&Matcher::new()).unwrap(), 1);
+    assert!(!dao.exists::<Note>(&matcher).unwrap());
+    let mut values = JsonObject::new();
+    values.set_str("title", "x");
+    assert_eq!(dao.update_where::<Note>(&matcher, values).unwrap(), 0);
+
+    let mut search = Search::new();
+    search.start = 0;
+    search.limit = 10;
+    assert_eq!(dao.list::<Note>(&mut search).unwrap().len(), 1);
+    assert_eq!(search.total, 1);
+
+    // with_deleted 包含已删除的数据
+    search.with_deleted();
+    assert_eq!(dao.list::<Note>(&mut search).unwrap().len(), 3);
+    assert_eq!(search.total, 3);
+    matcher.with_deleted();
+    let b = dao.find_one::<Note>(&matcher).unwrap().unwrap();
+    assert!(b.deleted_at.is_some());
+
+    // set 不修改已删除的数据，也不会通过实体的删除时间恢复数据
+    let edited = Note {
+        title: "edited".to_string(),
+        deleted_at: None,
+        ..b.clone()
+    };
+    assert_eq!(dao.set(edited).unwrap(), 0);
+    assert_eq!(dao.find_one::<Note>(&matcher).unwrap().unwrap(), b);
+    let mut c = dao.get::<Note, _>(3).unwrap().unwrap();
+    c.deleted_at = Some(1);
+    assert_eq!(dao.set(c).unwrap(), 1);
+    assert_eq!(dao.get::<Note, _>(3).unwrap().unwrap().deleted_at, None);
+
+    // upsert 同样不修改已删除的数据，也不修改删除时间
+    let upserted = Note {
+        title: "upserted".to_string(),
+        deleted_at: None,
+        ..b.clone()
+    };
+    assert_eq!(dao.upsert(upserted, &[]).unwrap(), 0);
+    assert_eq!(dao.find_one::<Note>(&matcher).unwrap().unwrap(), b);
+    let mut c = dao.get::<Note, _>(3).unwrap().unwrap();
+    c.deleted_at = Some(1);
+    assert_eq!(dao.upsert(c, &[]).unwrap(), 1);
+    assert_eq!(dao.get::<Note, _>(3).unwrap().unwrap().deleted_at, None);
+
+    // 恢复与物理删除
+    assert_eq!(dao.restore::<Note, _>(b.id).unwrap(), 1);
+    assert_eq!(dao.get::<Note, _>(b.id).unwrap().unwrap().deleted_at, None);
+    assert_eq!(dao.purge::<Note, _>(1).unwrap(), 1);
+    assert_eq!(dao.restore::<Note, _>(1).unwrap(), 0);
+    assert_eq!(dao.count::<Note>(Matcher::new().with_deleted()).unwrap(), 2);
+    matcher.clear();
+    assert!(!matcher.is_with_deleted());
+
+    // 未使用软删除的表
+    assert!(matches!(dao.restore::<Animal, _>(1), Err(Error::ArgError(_))));
+    dao.add(animal("cat", 1)).unwrap();
+    assert_eq!(dao.delete_by_id::<Animal, _>(1).unwrap(), 1);
+    assert_eq!(dao.count::<Animal>(Matcher::new().with_deleted()).unwrap(), 0);
+}
+
+#[derive(Debug, Clone, Default, Table)]
+#[table(name = "posts", hooks)]
+struct Post {
+    #[id(auto_increment)]
+    id: i64,
+    title: String,
+    locked: bool,
+    #[column(created_at)]
+    created_at: i64,
+    #[column(updated_at)]
+    updated_at: i64,
+    #[column(skip)]
+    loaded: bool,
+}
+
+impl Hooks for Post {
+    fn before_insert(&mut self) -> Result<(), Error> {
+        self.title = self.title.trim().to_string();
+        if self.title.is_empty() {
+            return Err(Error::ArgError("title is empty".to_string()));
+        }
+        Ok(())
+    }
+
+    fn before_update(&mut self) -> Result<(), Error> {
+        self.title = self.title.trim().to_string();
+        Ok(())
+    }
+
+    fn after_load(&mut self) -> Result<(), Error> {
+        self.loaded = true;
+        Ok(())
+    }
+
+    fn before_delete(&self) -> Result<(), Error> {
+        if self.locked {
+            return Err(Error::ArgError("post is locked".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn post(title: &str) -> Post {
+    Post {
+        title: title.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_hooks_and_timestamps() {
+    let dao = memory_dao();
+    dao.create_table_for::<Post>().unwrap();
+
+    // 添加时调用 before_insert，并写入创建时间与修改时间
+    let created = dao.insert_and_reload(post("  hello ")).unwrap();
+    assert_eq!(created.title, "hello");
+    assert!(created.loaded);
+    assert!(created.created_at > 0);
+    assert_eq!(created.updated_at, created.created_at);
+    assert!(matches!(dao.add(post(" ")), Err(Error::ArgError(_))));
+    assert_eq!(dao.count::<Post>(&Matcher::new()).unwrap(), 1);
+
+    // 已赋值的创建时间保留
+    let old = Post {
+        created_at: 100,
+        ..post("old")
+    };
+    let ids = dao.add_many(vec![old, post("new")]).unwrap();
+    let old = dao.get::<Post, _>(ids[0]).unwrap().unwrap();
+    assert_eq!(old.created_at, 100);
+    assert!(old.updated_at > 100);
+
+    // set 与 update_where 写入修改时间，不修改创建时间
+    dao.update_where::<Post>(
+        Matcher::new().and("id", Operator::Eq, old.id),
+        {
+            let mut values = JsonObject::new();
+            values.set_i64("updated_at", 1);
+            values
+        },
+    )
+    .unwrap();
+    assert!(dao.get::<Post, _>(old.id).unwrap().unwrap().updated_at > 1);
+    let changed = Post {
+        title: " renamed ".to_string(),
+        created_at: 0,
+        updated_at: 0,
+        ..old.clone()
+    };
+    dao.set(changed).unwrap();
+    let renamed = dao.get::<Post, _>(old.id).unwrap().unwrap();
+    assert_eq!(renamed.title, "renamed");
+    assert_eq!(renamed.created_at, 100);
+    assert!(renamed.updated_at > 100);
+
+    // upsert 冲突时保留创建时间
+    let conflict = Post {
+        id: old.id,
+        created_at: 5,
+        ..post("upserted")
+    };
+    dao.upsert(conflict, &[]).unwrap();
+    let upserted = dao.get::<Post, _>(old.id).unwrap().unwrap();
+    assert_eq!((upserted.title.as_str(), upserted.created_at), ("upserted", 100));
+
+    // before_delete 返回 Err 时不删除
+    let locked = Post {
+        locked: true,
+        ..upserted
+    };
+    dao.set(locked.clone()).unwrap();
+    assert!(matches!(dao.delete(locked), Err(Error::ArgError(_))));
+    assert!(dao.get::<Post, _>(old.id).unwrap().is_some());
+}
+
+#[derive(Debug, Clone, Default, Table)]
+#[table(name = "docs")]
+struct Doc {
+    #[id(auto_increment)]
+    id: i64,
+    body: String,
+    #[column(version)]
+    version: i64,
+}
+
+#[test]
+fn test_optimistic_lock() {
+    let dao = memory_dao();
+    dao.create_table_for::<Doc>().unwrap();
+    let id = dao.insert(Doc {
+        body: "v0".to_string(),
+        ..Default::default()
+    })
+    .unwrap();
+
+    // 两次读取同一条数据，先修改的成功并递增版本号，后修改的冲突
+    let first = dao.get::<Doc, _>(id).unwrap().unwrap();
+    let second = first.clone();
+    assert_eq!(
+        dao.set(Doc {
+            body: "first".to_string(),
+            ..first
+        })
+        .unwrap(),
+        1
+    );
+    let err = dao
+        .set(Doc {
+            body: "second".to_string(),
+            ..second
+        })
+        .unwrap_err();
+    assert!(err.is_conflict(), "{}", err);
+    let saved = dao.get::<Doc, _>(id).unwrap().unwrap();
+    assert_eq!((saved.body.as_str(), saved.version), ("first", 1));
+
+    // update_where 与 upsert 同样递增版本号
+    let mut matcher = Matcher::new();
+    matcher.and("id", Operator::Eq, id);
+    let mut values = JsonObject::new();
+    values.set_str("body", "where");
+    dao.update_where::<Doc>(&matcher, values).unwrap();
+    let upsert = |version| {
+        dao.upsert(
+            Doc {
+                id,
+                body: "upsert".to_string(),
+                version,
+            },
+            &[],
+        )
+    };
+    assert_eq!(upsert(2).unwrap(), 1);
+    let saved = dao.get::<Doc, _>(id).unwrap().unwrap();
+    assert_eq!((saved.body.as_str(), saved.version), ("upsert", 3));
+
+    // upsert 同样检查版本号，不覆盖其它连接的修改
+    assert!(upsert(2).unwrap_err().is_conflict());
+    assert_eq!(dao.get::<Doc, _>(id).unwrap().unwrap().version, 3);
+
+    // 数据已删除时同样返回冲突
+    dao.delete_by_id::<Doc, _>(id).unwrap();
+    assert!(dao.set(saved).unwrap_err().is_conflict());
+}
+
+#[derive(Debug, Clone, Default, Table)]
+#[table(name = "teams")]
+struct Team {
+    #[id(auto_increment)]
+    id: i64,
+    name: String,
+}
+
+#[derive(Debug, Clone, Default, Table)]
+#[table(name = "members")]
+struct Member {
+    #[id(auto_increment)]
+    id: i64,
+    name: String,
+    team_id: Option<i64>,
+    #[column(soft_delete)]
+    deleted_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Table)]
+#[table(name = "tags")]
+struct Tag {
+    #[id(auto_increment)]
+    id: i64,
+    name: String,
+}
+
+fn names<T, F: Fn(&T) -> &str>(list: &[Vec<T>], f: F) -> Vec<Vec<&str>> {
+    list.iter()
+        .map(|group| group.iter().map(&f).collect())
+        .collect()
+}
+
+#[test]
+fn test_relations() {
+    let dao = memory_dao();
+    dao.create_table_for::<Team>().unwrap();
+    dao.create_table_for::<Member>().unwrap();
+    dao.create_table_for::<Tag>().unwrap();
+    dao.batch("create table member_tags (member_id integer, tag_id integer)")
+        .unwrap();
+    for name in ["red", "blue", "empty"] {
+        dao.add(Team {
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    }
+    for (name, team_id) in [("ann", Some(1)), ("bob", Some(1)), ("cat", Some(2)), ("dan", None)] {
+        dao.add(Member {
+            name: name.to_string(),
+            team_id,
+            ..Default::default()
+        })
+        .unwrap();
+    }
+    for name in ["rust", "go"] {
+        dao.add(Tag {
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    }
+    dao.batch("insert into member_tags values (1, 1), (1, 2), (2, 1), (3, 2)")
+        .unwrap();
+    dao.delete_by_id::<Member, _>(2).unwrap();
+
+    // 一对多，排除已删除的成员
+    let teams = dao.list_all::<Team>().unwrap();
+    let members = dao
+        .load_related::<Team, Member>(&teams, &Relation::has_many("team_id"))
+        .unwrap();
+    assert_eq!(names(&members, |m| &m.name), vec![vec!["ann"], vec!["cat"], vec![]]);
+
+    // 属于，多个成员可以关联同一个团队，外键为 null 时没有关联
+    let members = dao.list_all::<Member>().unwrap();
+    let teams = dao
+        .load_related::<Member, Team>(&members, &Relation::belongs_to("team_id"))
+        .unwrap();
+    assert_eq!(names(&teams, |t| &t.name), vec![vec!["red"], vec!["blue"], vec![]]);
+
+    // 多对多
+    let tags_of = Relation::many_to_many("member_tags", "member_id", "tag_id");
+    let tags = dao.load_related::<Member, Tag>(&members, &tags_of).unwrap();
+    assert_eq!(names(&tags, |t| &t.name), vec![vec!["rust", "go"], vec!["go"], vec![]]);
+
+    // 关联字段不存在，或没有父实体
+    assert!(matches!(
+        dao.load_related::<Team, Member>(&[], &Relation::has_many("owner_id")),
+        Err(Error::ArgError(_))
+    ));
+    assert!(dao
+        .load_related::<Team, Member>(&[], &Relation::has_many("team_id"))
+        .unwrap()
+        .is_empty());
+
+    // 按关联数据过滤，一对多不产生重复的行
+    let mut search = Search::new();
+    search.with_related::<Team, Member>(&Relation::has_many("team_id"), Matcher::new());
+    let teams = dao.list::<Team>(&mut search).unwrap();
+    assert_eq!(teams.iter().map(|t| t.id).collect::<Vec<_>>(), vec![1, 2]);
+
+    let mut rust = Matcher::new();
+    rust.and("name", Operator::Eq, "rust");
+    let mut search = Search::new();
+    search.with_related::<Member, Tag>(&tags_of, rust);
+    search.with_deleted();
+    let members = dao.list::<Member>(&mut search).unwrap();
+    assert_eq!(members.iter().map(|m| m.id).collect::<Vec<_>>(), vec![1, 2]);
+
+    // 已删除的成员默认不参与过滤
+    let mut bob = Matcher::new();
+    bob.and("name", Operator::Eq, "bob");
+    let mut matcher = Matcher::new();
+    matcher.and_related::<Team, Member>(&Relation::has_many("team_id"), bob.clone());
+    assert_eq!(dao.count::<Team>(&matcher).unwrap(), 0);
+    bob.with_deleted();
+    let mut matcher = Matcher::new();
+    matcher.and_related::<Team, Member>(&Relation::has_many("team_id"), bob);
+    assert_eq!(dao.count::<Team>(&matcher).unwrap(), 1);
+}
+
+#[derive(Debug, Clone, Default, Table)]
+#[table(name = "articles")]
+struct Article {
+    #[id(auto_increment)]
+    id: i64,
+    #[column(searchable)]
+    title: String,
+    #[column(searchable)]
+    body: Option<String>,
+    status: i64,
+    #[column(soft_delete)]
+    deleted_at: Option<i64>,
+}
+
+fn article(title: &str, body: &str, status: i64) -> Article {
+    Article {
+        title: title.to_string(),
+        body: Some(body.to_string()),
+        status,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_full_text_search() {
+    let dao = memory_dao();
+    dao.create_table_for::<Article>().unwrap();
+    // 建立索引前已有的数据
+    dao.add(article("rust orm", "an orm for sqlite written in rust", 1))
+        .unwrap();
+    dao.create_fts_for::<Article>().unwrap();
+    dao.create_fts_for::<Article>().unwrap();
+    dao.add_many(vec![
+        article("go notes", "rust is mentioned once", 1),
+        article("cooking", "nothing to see here", 1),
+        article("rust draft", "rust rust rust", 0),
+    ])
+    .unwrap();
+
+    // 按相关度排序，输出标记后的内容与片段
+    let search = TextSearch::new("rust")
+        .highlight("title")
+        .snippet("body", 3)
+        .marker("[", "]");
+    let hits = dao.search_text::<Article>(&search).unwrap();
+    assert_eq!(hits.len(), 3);
+    assert!(hits.windows(2).all(|w| w[0].rank <= w[1].rank));
+    let orm = hits.iter().find(|h| h.entity.id == 1).unwrap();
+    assert_eq!(orm.highlights["title"], "[rust] orm");
+    assert!(orm.snippets["body"].contains("[rust]"));
+
+    // 过滤条件与分页
+    let mut published = Matcher::new();
+    published.and("status", Operator::Eq, 1);
+    let hits = dao
+        .search_text::<Article>(&TextSearch::new("rust").filter(published).limit(0, 1))
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].entity.status, 1);
+
+    // 修改、软删除与物理删除后索引保持同步
+    let mut cooking = dao.get::<Article, _>(3).unwrap().unwrap();
+    cooking.title = "rust cooking".to_string();
+    dao.set(cooking).unwrap();
+    dao.delete_by_id::<Article, _>(4).unwrap();
+    dao.purge::<Article, _>(2).unwrap();
+    let mut matcher = Matcher::new();
+    matcher.and("articles", Operator::Match, "rust");
+    let mut search = Search::new();
+    search.matcher = matcher.clone();
+    let ids: Vec<i64> = dao
+        .list::<Article>(&mut search)
+        .unwrap()
+        .iter()
+        .map(|a| a.id)
+        .collect();
+    assert_eq!(ids, vec![1, 3]);
+    matcher.with_deleted();
+    assert_eq!(dao.count::<Article>(&matcher).unwrap(), 3);
+
+    // 用户输入中的 FTS5 语法需要经过 quote 处理
+    assert!(matches!(
+        dao.search_text::<Article>(&TextSearch::new("\"rust")),
+        Err(Error::SqlError(_))
+    ));
+    let hits = dao
+        .search_text::<Article>(&TextSearch::new(&quote("\"rust OR")))
+        .unwrap();
+    assert!(hits.is_empty());
+    assert!(matches!(
+        dao.search_text::<Article>(&TextSearch::new("rust").highlight("status")),
+        Err(Error::ArgError(_))
+    ));
+
+    // 客户端提交的 q 参数
+    let spec = SearchSpec::from_query("q=rust%20cooking").unwrap();
+    let list = dao
+        .list::<Article>(&mut spec.to_search::<Article>().unwrap())
+        .unwrap();
+    assert_eq!(list.iter().map(|a| a.id).collect::<Vec<_>>(), vec![3]);
+    assert!(matches!(spec.to_search::<Team>(), Err(Error::ArgError(_))));
+
+    dao.drop_fts_for::<Article>().unwrap();
+    dao.add(article("rust again", "", 1)).unwrap();
+}
+
+fn animal_names(dao: &Dao<Connection>) -> Vec<String> {
+    dao.list_all::<Animal>()
+        .unwrap()
+        .into_iter()
+        .map(|a| a.name)
+        .collect()
+}
+
+#[test]
+fn test_backup_and_restore() {
+    let dir = std::env::temp_dir().join(format!("lib_sql_backup_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        datasource: dir.join("data.db").to_string_lossy().to_string(),
+        backup: Some(BackupConfig {
+            dir: None,
+            keep: Some(2),
+        }),
+        ..Default::default()
+    };
+    let dao = Dao::open(config.clone()).unwrap();
+    dao.create_table_for::<Animal>().unwrap();
+    dao.add(animal("cat", 1)).unwrap();
+
+    // 备份到指定文件，其它连接在备份期间可以继续写入
+    let snapshot = dir.join("snapshot.db");
+    dao.backup_to(&snapshot).unwrap();
+    let other = Dao::open(config).unwrap();
+    other.add(animal("dog", 2)).unwrap();
+    assert_eq!(animal_names(&dao), vec!["cat", "dog"]);
+
+    // 按时间命名的备份只保留最近的 2 个
+    let backups: Vec<_> = (0..3).map(|_| dao.backup().unwrap()).collect();
+    let list = dao.list_backups().unwrap();
+    assert_eq!(list, backups[1..].to_vec());
+    assert!(list.iter().all(|p| p.starts_with(dir.join("backup"))));
+    assert_eq!(dao.prune_backups(1).unwrap(), backups[1..2].to_vec());
+
+    // 恢复后所有连接读取到备份时的数据
+    dao.restore_from(&snapshot).unwrap();
+    assert_eq!(animal_names(&other), vec!["cat"]);
+    dao.restore_from(&backups[2]).unwrap();
+    assert_eq!(animal_names(&dao), vec!["cat", "dog"]);
+
+    // 维护命令
+    assert!(dao.integrity_check().unwrap().is_empty());
+    dao.vacuum().unwrap();
+    dao.analyze().unwrap();
+
+    // 备份文件不存在、已损坏，或在事务中恢复
+    assert!(matches!(
+        dao.restore_from(dir.join("missing.db")),
+        Err(Error::NotFound(_))
+    ));
+    let broken = dir.join("broken.db");
+    std::fs::write(&broken, "not a database").unwrap();
+    assert!(dao.restore_from(&broken).is_err());
+    let result = dao.transaction(|tx| tx.restore_from(&snapshot));
+    assert!(matches!(result, Err(Error::ArgError(_))));
+    assert_eq!(animal_names(&dao), vec!["cat", "dog"]);
+
+    // 同一秒内的序号按数值排序，不是 backup 生成的文件不参与清理
+    let backup_dir = dir.join("backup");
+    let named: Vec<_> = ["data-20200101-000000-10.db", "data-20200101-000000-2.db", "data-old.db"]
+        .iter()
+        .map(|name| backup_dir.join(name))
+        .collect();
+    for path in &named {
+        std::fs::write(path, "").unwrap();
+    }
+    let list = dao.list_backups().unwrap();
+    assert_eq!(list, vec![named[1].clone(), named[0].clone(), backups[2].clone()]);
+    assert_eq!(dao.prune_backups(1).unwrap(), vec![named[1].clone(), named[0].clone()]);
+    assert!(named[2].exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Table)]
+#[table(name = "products")]
+struct Product {
+    #[id(auto_increment)]
+    id: i64,
+    #[column(unique)]
+    name: String,
+    price: f64,
+    stock: i64,
+    active: bool,
+    note: Option<String>,
+}
+
+fn product(name: &str, price: f64, stock: i64, note: Option<&str>) -> Product {
+    Product {
+        name: name.to_string(),
+        price,
+        stock,
+        active: true,
+        note: note.map(|n| n.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_export_and_import() {
+    let dao = memory_dao();
+    dao.create_table_for::<Product>().unwrap();
+    dao.add_many(vec![
+        product("pen", 1.5, 3, Some("blue, \"fine\"\nline")),
+        product("book", 12.0, 0, None),
+        product("cup", 4.25, 7, Some("")),
+    ])
+    .unwrap();
+    let products = dao.list_all::<Product>().unwrap();
+
+    // 导出后导入到另一个数据库，数据与主键保持一致
+    for format in [Format::Json, Format::JsonLines, Format::Csv] {
+        let data = dao.export::<Product>(&Search::new(), format).unwrap();
+        let other = memory_dao();
+        other.create_table_for::<Product>().unwrap();
+        let report = other
+            .import::<Product>(&data, format, &ImportOptions::new())
+            .unwrap();
+        assert!(report.is_ok(), "{:?} {:?}", format, report.errors);
+        assert_eq!(
+            (report.total, report.imported, report.committed),
+            (3, 3, true)
+        );
+        assert_eq!(other.list_all::<Product>().unwrap(), products);
+    }
+    let csv = dao.export::<Product>(&Search::new(), Format::Csv).unwrap();
+    assert_eq!(
+        csv,
+        "id,name,price,stock,active,note\n\
+         1,pen,1.5,3,true,\"blue, \"\"fine\"\"\nline\"\n\
+         2,book,12.0,0,true,\n\
+         3,cup,4.25,7,true,\"\"\n"
+    );
+    let mut search = Search::new();
+    search.matcher.and("stock", Operator::Gt, 0);
+    let json = dao.export::<Product>(&search, Format::JsonLines).unwrap();
+    assert_eq!(json.lines().count(), 2);
+
+    // 字段映射与类型转换，没有主键时由数据库生成
+    let data = "title,cost,qty,active,extra\nlamp,\" 20 \",2,false,x\nmug,3,,1,y\n";
+    let options = ImportOptions::new()
+        .map("title", "name")
+        .map("cost", "price")
+        .map("qty", "stock")
+        .skip("extra");
+    let report = dao.import::<Product>(data, Format::Csv, &options).unwrap();
+    assert_eq!(
+        report.errors[0].to_string(),
+        "row 2: column stock can not be decoded as i64"
+    );
+    assert!(!report.committed);
+    let data = data.replace(",,", ",5,");
+    let report = dao.import::<Product>(&data, Format::Csv, &options).unwrap();
+    assert!(report.committed);
+    let lamp = dao.get::<Product, _>(4).unwrap().unwrap();
+    assert_eq!((lamp.price, lamp.stock, lamp.active), (20.0, 2, false));
+
+    // 试导入报告每行的错误，不写入数据
+    let data = r#"{"name":"fork","price":"cheap","stock":1,"active":true}
+{"name":"pen","price":1,"stock":1,"active":true}
+{"name":"plate","price":2,"stock":1,"active":true,"color":"red"}
+not json
+{"name":"spoon","price":1,"stock":1,"active":true}"#;
+    let report = dao
+        .import::<Product>(data, Format::JsonLines, &ImportOptions::new().dry_run(true))
+        .unwrap();
+    let rows: Vec<usize> = report.errors.iter().map(|e| e.row).collect();
+    assert_eq!(rows, vec![1, 2, 3, 4]);
+    assert_eq!(
+        report.errors[0].message,
+        "column price expected REAL, got \"cheap\""
+    );
+    assert!(report.errors[2].message.contains("unknown column color"));
+    assert_eq!(
+        (report.total, report.imported, report.committed),
+        (5, 1, false)
+    );
+    let report = dao
+        .import::<Product>(
+            data.lines().last().unwrap(),
+            Format::JsonLines,
+            &ImportOptions::new().dry_run(true),
+        )
+        .unwrap();
+    assert!(report.is_ok() && !report.committed);
+    assert_eq!(dao.count::<Product>(&Matcher::new()).unwrap(), 5);
+
+    // 数据整体无法解析或映射到不存在的字段
+    let options = ImportOptions::new();
+    assert!(matches!(
+        dao.import::<Product>("[{", Format::Json, &options),
+        Err(Error::ArgError(_))
+    ));
+    assert!(matches!(
+        dao.import::<Product>("name\n\"pen", Format::Csv, &options),
+        Err(Error::ArgError(_))
+    ));
+    assert!(matches!(
+        dao.import::<Product>("[]", Format::Json, &options.map("a", "b")),
+        Err(Error::ArgError(_))
+    ));
+}
+
+#[test]
+fn test_memory_and_temp_datasources() {
+    // 每个内存 Dao 是独立的数据库
+    let dao = memory_dao();
+    dao.add(animal("cat", 1)).unwrap();
+    assert!(Dao::memory().unwrap().list_all::<Animal>().is_err());
+
+    // 连接池的所有连接共享同一个内存数据库，不同的连接池互不影响
+    let config = Config {
+        pool: Some(PoolConfig {
+            max_size: Some(2),
+            ..Default::default()
+        }),
+        ..Config::memory()
+    };
+    let pool = Pool::new(config.clone()).unwrap();
+    let (a, b) = (pool.get().unwrap(), pool.get().unwrap());
+    a.create_table(ANIMALS_SQL).unwrap();
+    a.add(animal("dog", 2)).unwrap();
+    assert_eq!(animal_names(&b), vec!["dog"]);
+    let other = Pool::new(config).unwrap();
+    assert!(other.get().unwrap().list_all::<Animal>().is_err());
+
+    // 临时文件在 drop 时删除
+    let db = TempDatabase::new();
+    let path = db.path().to_path_buf();
+    {
+        let pool = db.pool().unwrap();
+        pool.get().unwrap().create_table(ANIMALS_SQL).unwrap();
+        db.dao().unwrap().add(animal("fox", 3)).unwrap();
+        assert_eq!(animal_names(&pool.get().unwrap()), vec!["fox"]);
+    }
+    assert!(path.exists());
+    drop(db);
+    assert!(!path.exists());
+
+    // 无法创建目录或打开文件时返回错误
+    let file = TempDatabase::new();
+    std::fs::write(file.path(), "").unwrap();
+    let nested = file.path().join("data.db");
+    let result = Dao::open(Config::new(&nested.to_string_lossy()));
+    assert!(matches!(result, Err(Error::ConfigError(_))));
+}
+
+#[test]
+fn test_load_fixtures() {
+    let dir = std::env::temp_dir().join(format!("lib_sql_fixtures_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("01_animals.json"),
+        r#"[{"id":7,"name":"cat","age":1},{"name":"dog","age":2}]"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("02_animals.csv"), "name,age\nfox,3\n").unwrap();
+    std::fs::write(dir.join("readme.txt"), "not a fixture").unwrap();
+
+    let dao = memory_dao();
+    assert_eq!(dao.load_fixtures(&dir).unwrap(), 3);
+    let ages: Vec<(i64, String, i32)> = dao
+        .list_all::<Animal>()
+        .unwrap()
+        .into_iter()
+        .map(|a| (a.id, a.name, a.age))
+        .collect();
+    assert_eq!(
+        ages,
+        vec![
+            (7, "cat".to_string(), 1),
+            (8, "dog".to_string(), 2),
+            (9, "fox".to_string(), 3)
+        ]
+    );
+
+    // 出错时整个文件回滚
+    let data = "{\"name\":\"owl\",\"age\":4}\n{\"name\":\"bat\"}\n";
+    assert!(dao.load_fixture("animals", data, Format::JsonLines).is_err());
+    let data = "{\"name\":\"owl\",\"age\":4}\nnot json\n";
+    assert!(matches!(
+        dao.load_fixture("animals", data, Format::JsonLines),
+        Err(Error::ArgError(_))
+    ));
+    assert_eq!(animal_names(&dao), vec!["cat", "dog", "fox"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_error_kinds() {
+    let dao = memory_dao();
+    dao.create_table_for::<UserAccount>().unwrap();
+    let account = UserAccount {
+        id: "a".to_string(),
+        username: "demo".to_string(),
+        email: String::new(),
+        balance: 0.0,
+        nickname: None,
+    };
+    dao.add(account.clone()).unwrap();
+
+    // 唯一约束冲突，带上字段名与结果码，语句中只有占位符
+    let e = dao
+        .add(UserAccount {
+            id: "b".to_string(),
+            ..account.clone()
+        })
+        .unwrap_err();
+    assert!(e.is_unique_violation());
+    assert_eq!(
+        e.sql_kind(),
+        Some(&SqlErrorKind::UniqueViolation {
+            table: "user_account".to_string(),
+            columns: vec!["username".to_string()],
+        })
+    );
+    let Error::SqlError(sql_error) = &e else {
+        panic!("{:?}", e);
+    };
+    assert_eq!(sql_error.code, Some(19));
+    assert!(!sql_error.sql.as_ref().unwrap().contains("demo"));
+    assert!(e.to_string().contains("UNIQUE constraint failed: user_account.username"));
+
+    let e = dao.query_raw("insert into animals (age) values (1)", &[]).unwrap_err();
+    assert_eq!(
+        e.sql_kind(),
+        Some(&SqlErrorKind::NotNullViolation {
+            table: "animals".to_string(),
+            column: "name".to_string(),
+        })
+    );
+
+    // 读取时字段类型不符
+    dao.query_raw("update user_account set balance = 'x'", &[]).unwrap();
+    let e = dao.get::<UserAccount, _>("a").unwrap_err();
+    assert!(matches!(
+        e,
+        Error::Decode { ref column, expected: "f64" } if column == "balance"
+    ));
+    assert_eq!(e.to_string(), "column balance can not be decoded as f64");
+
+    let e = dao.query_raw("select * from missing", &[]).unwrap_err();
+    assert_eq!(e.sql_kind(), Some(&SqlErrorKind::Other));
+}
+
+#[test]
+fn test_aggregate() {
+    let dao = memory_dao();
+    dao.add_many(vec![animal("cat", 1), animal("cat", 3), animal("dog", 2)])
+        .unwrap();
+
+    let mut matcher = Matcher::new();
+    matcher.and("age", Operator::Lt, 10);
+    let search = Aggregate::new()
+        .group_by("name")
+        .count("cnt")
+        .sum("age", "total")
+        .avg("age", "average")
+        .min("age", "youngest")
+        .max("age", "oldest")
+        .filter(matcher)
+        .order_by("name", false)
+        .to_search();
+    let rows = dao.aggregate::<Animal>(&search).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get_str("name"), Some("cat"));
+    assert_eq!(rows[0].get_i64("cnt"), Some(2));
+    assert_eq!(rows[0].get_i64("total"), Some(4));
+    assert_eq!(rows[0].get_f64("average"), Some(2.0));
+    assert_eq!(rows[0].get_i64("youngest"), Some(1));
+    assert_eq!(rows[0].get_i64("oldest"), Some(3));
+    assert_eq!(rows[1].get_str("name"), Some("dog"));
+    assert_eq!(rows[1].get_i64("cnt"), Some(1));
+
+    // 不分组时统计全表
+    let rows = dao
+        .aggregate::<Animal>(&Aggregate::new().count("cnt").to_search())
+        .unwrap();
+    assert_eq!(rows[0].get_i64("cnt"), Some(3));
+
+    let rows = dao
+        .query_raw(
+            "select name, max(age) as oldest from animals where age > ? group by name",
+            &[&1],
+        )
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    let rows = dao.query_raw("select null as n", &[]).unwrap();
+    assert_eq!(rows[0].to_json(), r#"{"n":null}"#);
+    assert!(dao.query_raw("select * from missing", &[]).is_err());
+}
+
+#[test]
+fn test_upsert_and_update_where() {
+    let dao = memory_dao();
+    dao.create_table("CREATE UNIQUE INDEX idx_animals_name ON animals (name)")
+        .unwrap();
+    dao.add_many(vec![animal("cat", 1), animal("dog", 2)]).unwrap();
+
+    // 按唯一字段冲突时更新其余字段
+    assert_eq!(dao.upsert(animal("cat", 5), &["name"]).unwrap(), 1);
+    assert_eq!(dao.upsert(animal("fox", 3), &["name"]).unwrap(), 1);
+    let mut matcher = Matcher::new();
+    matcher.and("name", Operator::Eq, "cat");
+    let cat = dao.find_one::<Animal>(&matcher).unwrap().unwrap();
+    assert_eq!((cat.id, cat.age), (1, 5));
+    assert_eq!(dao.count::<Animal>(&Matcher::new()).unwrap(), 3);
+
+    // 按主键冲突
+    let dog = Animal { id: 2, name: "wolf".to_string(), age: 4 };
+    dao.upsert(dog.clone(), &[]).unwrap();
+    assert_eq!(dao.get::<Animal, _>(2).unwrap(), Some(dog));
+    assert!(matches!(
+        dao.upsert(animal("owl", 1), &["color"]),
+        Err(Error::ArgError(_))
+    ));
+
+    // 只更新提供的字段
+    let mut matcher = Matcher::new();
+    matcher.and("age", Operator::Ge, 4);
+    let mut values = JsonObject::new();
+    values.set_i32("age", 9);
+    assert_eq!(dao.update_where::<Animal>(&matcher, values).unwrap(), 2);
+    let wolf = dao.get::<Animal, _>(2).unwrap().unwrap();
+    assert_eq!((wolf.name.as_str(), wolf.age), ("wolf", 9));
+
+    let mut values = JsonObject::new();
+    values.set_str("color", "red");
+    assert!(matches!(
+        dao.update_where::<Animal>(&matcher, values.clone()),
+        Err(Error::ArgError(_))
+    ));
+    assert!(matches!(
+        dao.update_where::<Animal>(&Matcher::new(), values),
+        Err(Error::ArgError(_))
+    ));
+}
+
+// 在当前线程上等待 future 完成，测试中代替异步运行时
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+// 记录每次执行的语句、参数个数、行数及是否出错
+#[derive(Default)]
+struct Recorder(Mutex<Vec<(String, usize, usize, bool)>>);
+
+impl QueryObserver for Recorder {
+    fn on_query(&self, event: &QueryEvent) {
+        self.0.lock().unwrap().push((
+            event.sql.to_string(),
+            event.params,
+            event.rows,
+            event.error.is_some(),
+        ));
+    }
+}
+
+#[test]
+fn test_query_observer() {
+    let mut dao = memory_dao();
+    let recorder = Arc::new(Recorder::default());
+    dao.set_observer(recorder.clone());
+
+    dao.add(animal("secret", 1)).unwrap();
+    dao.add_many(vec![animal("cat", 2), animal("dog", 3)]).unwrap();
+    let mut matcher = Matcher::new();
+    matcher.and("age", Operator::Gt, 1);
+    assert_eq!(dao.count::<Animal>(&matcher).unwrap(), 2);
+    dao.list_all::<Animal>().unwrap();
+    assert!(dao.query_raw("select * from missing", &[]).is_err());
+
+    let events = recorder.0.lock().unwrap();
+    let events: Vec<(&str, usize, usize, bool)> = events
+        .iter()
+        .map(|(sql, params, rows, error)| (sql.as_str(), *params, *rows, *error))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            ("insert into animals (`name`,`age`) values (?,?)", 2, 1, false),
+            ("BEGIN DEFERRED", 0, 0, false),
+            ("insert into animals (`name`,`age`) values (?,?)", 4, 2, false),
+            ("COMMIT", 0, 0, false),
+            ("SELECT count(*) AS cnt FROM animals where ((age > ?))", 1, 1, false),
+            ("SELECT * FROM animals", 0, 3, false),
+            ("select * from missing", 0, 0, true),
+        ]
+    );
+    // 语句中不包含绑定的参数值
+    assert!(events.iter().all(|(sql, ..)| !sql.contains("secret")));
+}
+
+#[test]
+fn test_async_dao() {
+    let path = std::env::temp_dir().join(format!("lib_sql_async_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let config = Config {
+        datasource: path.to_string_lossy().to_string(),
+        pool: Some(PoolConfig {
+            max_size: Some(2),
+            timeout: Some(1000),
+            pragmas: Some(vec!["busy_timeout = 1000".to_string()]),
+        }),
+        ..Default::default()
+    };
+    let dao = AsyncDao::new(Pool::new(config).unwrap());
+    block_on(dao.run(|dao| {
+        dao.create_table("CREATE TABLE animals (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, age INTEGER NOT NULL)")
+    }))
+    .unwrap();
+
+    let fox = block_on(dao.insert_and_reload(animal("fox", 3))).unwrap();
+    assert_eq!(fox.id, 1);
+    let futures: Vec<_> = (0..4).map(|i| dao.insert(animal("cat", i))).collect();
+    for future in futures {
+        block_on(future).unwrap();
+    }
+    let mut matcher = Matcher::new();
+    matcher.and("name", Operator::Eq, "cat");
+    assert_eq!(block_on(dao.count::<Animal>(matcher)).unwrap(), 4);
+    assert_eq!(block_on(dao.get::<Animal, _>(1)).unwrap(), Some(fox));
+
+    // 事务失败回滚，任务 panic 时返回错误且工作线程继续可用
+    let result = block_on(dao.transaction(|tx| {
+        tx.add(animal("owl", 1))?;
+        Err::<(), _>(Error::ArgError("rollback".to_string()))
+    }));
+    assert!(result.is_err());
+    assert!(block_on(dao.run(|_| -> Result<(), Error> { panic!("boom") })).is_err());
+    assert_eq!(block_on(dao.list_all::<Animal>()).unwrap().len(), 5);
+
+    drop(dao);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_condition_tree() {
+    let cond = Condition::new("name", Operator::StartsWith, "c").and(
+        Condition::new("age", Operator::Between, [1, 3])
+            .or(!Condition::new("name", Operator::Glob, "*x")),
+    );
+    let query = cond.parse();
+    assert_eq!(
+        query.sql,
+        "((name like ?) and ((age between ? and ?) or (not (name glob ?))))"
+    );
+    assert_eq!(query.params.len(), 4);
+    // 空的分组不产生条件
+    assert!(Condition::all(vec![Condition::any(vec![])]).parse().is_empty());
+
+    let mut matcher = Matcher::new();
+    matcher.and("flags", Operator::Land, 5);
+    assert_eq!(matcher.parse().sql, "(((~flags & ?) = 0))");
+    assert_eq!(matcher.parse().params.len(), 1);
+
+    let dao = memory_dao();
+    dao.create_table("ALTER TABLE animals ADD COLUMN owner TEXT").unwrap();
+    dao.add_many(vec![animal("Cat", 1), animal("cow", 3), animal("fox", 5)])
+        .unwrap();
+    dao.batch("UPDATE animals SET owner = 'tom' WHERE name = 'fox'").unwrap();
+
+    let names = |matcher: &Matcher| -> Vec<String> {
+        let mut search = Search {
+            matcher: matcher.clone(),
+            ..Default::default()
+        };
+        search.sort.add("id", false);
+        dao.list::<Animal>(&mut search)
+            .unwrap()
+            .into_iter()
+            .map(|a| a.name)
+            .collect()
+    };
+
+    let mut matcher = Matcher::new();
+    matcher.and("name", Operator::EqNoCase, "cat");
+    assert_eq!(names(&matcher), vec!["Cat"]);
+
+    let mut matcher = Matcher::new();
+    matcher.and("owner", Operator::IsNull, ());
+    matcher.and("age", Operator::Between, [2, 9]);
+    assert_eq!(names(&matcher), vec!["cow"]);
+
+    let mut matcher = Matcher::new();
+    matcher.and("name", Operator::EndsWith, "x");
+    matcher.or_cond(
+        Condition::new("age", Operator::Land, 1).and(!Condition::new("name", Operator::Glob, "C*")),
+    );
+    assert_eq!(names(&matcher), vec!["fox"]);
+
+    let mut inner = Matcher::new();
+    inner.or("owner", Operator::IsNotNull, ());
+    inner.or("age", Operator::Lt, 2);
+    let mut matcher = Matcher::new();
+    matcher.and_not(inner);
+    assert_eq!(names(&matcher), vec!["cow"]);
+}
+
+#[test]
+fn test_search_spec() {
+    assert_eq!(UserAccount::filterable(), vec!["username", "balance"]);
+    assert!(Animal::filterable().is_empty());
+
+    let spec = SearchSpec::from_query(
+        "filter[username][starts]=to%27m&filter[balance][between]=1,10&sort=-balance,username&page=2&size=2&token=x",
+    )
+    .unwrap();
+    assert_eq!(
+        spec.filter["username"],
+        FieldFilter::Ops([("starts".to_string(), FilterValue::Text("to'm".to_string()))].into())
+    );
+    let search = spec.to_search::<UserAccount>().unwrap();
+    assert_eq!(
+        search.parse("user_account").sql,
+        "select * from user_account where ((balance between ? and ?) and (username like ?)) order by balance desc,username asc limit 2,2"
+    );
+
+    // json 请求体与查询字符串等价
+    let json: SearchSpec = serde_json::from_str(
+        r#"{"filter": {"username": "tom", "balance": {"in": [1, 2.5]}}, "sort": "username"}"#,
+    )
+    .unwrap();
+    assert_eq!(json.filter["username"], FieldFilter::Value(FilterValue::Text("tom".to_string())));
+    let search = json.to_search::<UserAccount>().unwrap();
+    assert_eq!((search.start, search.limit), (0, 20));
+    assert_eq!(search.matcher.parse().params.len(), 3);
+
+    // 白名单之外的字段、未知操作符与非法分页都会被拒绝
+    for query in [
+        "filter[email]=a",
+        "filter[username][drop]=a",
+        "sort=email",
+        "filter[balance][between]=1",
+        "page=0",
+        "size=1000",
+    ] {
+        let result = SearchSpec::from_query(query).and_then(|s| s.to_search::<UserAccount>());
+        assert!(matches!(result, Err(Error::ArgError(_))), "{}", query);
+    }
+    assert!(SearchSpec::from_query("page=x").is_err());
+    // 不完整或非十六进制的 %XX 保留原样，+ 解码为空格
+    for (query, q) in [("q=%41", "A"), ("q=%+1", "% 1"), ("q=a%4", "a%4"), ("q=+", " ")] {
+        let spec = SearchSpec::from_query(query).unwrap();
+        assert_eq!(spec.q.as_deref(), Some(q), "{}", query);
+    }
+    // 页码过大时偏移量溢出
+    for page in [usize::MAX, isize::MAX as usize / 20 + 2] {
+        let query = format!("page={}", page);
+        let result = SearchSpec::from_query(&query).and_then(|s| s.to_search::<UserAccount>());
+        assert!(
+            matches!(&result, Err(Error::ArgError(e)) if e == "page out of range"),
+            "{}",
+            query
+        );
+    }
+
+    let dao = memory_dao();
+    dao.create_table_for::<UserAccount>().unwrap();
+    for (i, name) in ["tom", "tony", "jerry"].iter().enumerate() {
+        dao.add(UserAccount {
+            id: format!("u{}", i),
+            username: name.to_string(),
+            email: String::new(),
+            balance: i as f64,
+            nickname: None,
+        })
+        .unwrap();
+    }
+    let mut search = SearchSpec::from_query("filter[username][starts]=to&sort=-balance&size=1")
+        .and_then(|s| s.to_search::<UserAccount>())
+        .unwrap();
+    let list = dao.list::<UserAccount>(&mut search).unwrap();
+    assert_eq!(search.total, 2);
+    assert_eq!(list[0].username, "tony");
+
+    // 使用上一页的游标，不统计总数
+    let query = format!(
+        "filter[username][starts]=to&sort=-balance&size=1&count=false&after={}",
+        search.next_cursor.unwrap()
+    );
+    let mut search = SearchSpec::from_query(&query)
+        .and_then(|s| s.to_search::<UserAccount>())
+        .unwrap();
+    let list = dao.list::<UserAccount>(&mut search).unwrap();
+    assert_eq!((list[0].username.as_str(), search.total), ("tom", 0));
+}
+
+#[test]
+fn test_keyset_pagination() {
+    let dao = memory_dao();
+    let ages = [3, 1, 3, 2, 3, 1, 2];
+    dao.add_many(ages.iter().enumerate().map(|(i, age)| animal(&format!("a{}", i), *age)).collect())
+        .unwrap();
+
+    let mut search = Search::default();
+    search.sort.add("age", true);
+    search.limit = 3;
+    search.skip_total = true;
+
+    // 翻页过程中插入数据不会导致重复或遗漏
+    let mut ids = vec![];
+    loop {
+        let list = dao.list::<Animal>(&mut search).unwrap();
+        ids.extend(list.iter().map(|a| a.id));
+        if ids.len() == 3 {
+            dao.add(animal("late", 0)).unwrap();
+        }
+        match search.next_cursor.clone() {
+            Some(cursor) => search.after(&cursor),
+            None => break,
+        };
+    }
+    assert_eq!(ids, vec![1, 3, 5, 4, 7, 2, 6, 8]);
+    assert_eq!(search.total, 0);
+
+    // 游标模式下仍可统计总数，只设置 limit 时不统计
+    let mut search = Search::default();
+    search.sort.add("age", false);
+    search.limit = 2;
+    dao.list::<Animal>(&mut search).unwrap();
+    assert_eq!(search.total, 0);
+    search.start = 0;
+    let first = dao.list::<Animal>(&mut search).unwrap();
+    assert_eq!(search.total, 8);
+    let cursor = search.next_cursor.clone().unwrap();
+    search.after(&cursor);
+    let second = dao.list::<Animal>(&mut search).unwrap();
+    assert_eq!((first[1].id, second[0].id), (2, 6));
+
+    search.after("zz");
+    assert!(matches!(dao.list::<Animal>(&mut search), Err(Error::ArgError(_))));
+}
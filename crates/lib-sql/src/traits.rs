@@ -1,14 +1,273 @@
+use std::fmt::Display;
+
 use lib_json::object::JsonObject;
 
-use crate::utils::Search;
+use crate::relation::Relation;
+use crate::utils::{Matcher, Search, ToType};
+
+/// 派生 Table，支持 `#[table(name = "...")]`、`#[id(auto_increment)]`、`#[column(rename = "...")]`、`#[column(skip)]`，
+/// 以及描述字段的 `#[column(unique, index, default = "0", sql_type = "TEXT")]`、
+/// 允许客户端过滤与排序的 `#[column(filterable, sortable)]`，参与全文检索的 `#[column(searchable)]`，
+/// 保存软删除时间的 `#[column(soft_delete)]`，自动维护的时间戳 `#[column(created_at)]`、`#[column(updated_at)]`，
+/// 乐观锁的版本号 `#[column(version)]`，以及使用 Hooks 的 `#[table(hooks)]`
+pub use lib_sql_derive::Table;
 
 /// 统一错误类
 #[derive(Debug)]
 pub enum Error {
+    /// 实体转换失败
     ParseError,
-    SqlError(String),
+    /// 执行语句出错，见 SqlError
+    SqlError(SqlError),
     ConfigError(String),
     ArgError(String),
+    PoolError(String),
+    /// 实体与数据库表结构不一致
+    SchemaError(String),
+    /// 要读取或修改的数据不存在
+    NotFound(String),
+    /// 乐观锁冲突，修改时数据已被其它操作修改或删除，需要重新读取后再修改
+    Conflict(String),
+    /// 读取行数据时字段缺失，或无法转为字段的类型
+    Decode {
+        column: String,
+        expected: &'static str,
+    },
+}
+
+impl Error {
+    /// 语句执行出错时的分类，其它错误返回 None
+    pub fn sql_kind(&self) -> Option<&SqlErrorKind> {
+        match self {
+            Error::SqlError(e) => Some(&e.kind),
+            _ => None,
+        }
+    }
+
+    /// 是否违反了唯一约束，如重复的用户名
+    pub fn is_unique_violation(&self) -> bool {
+        matches!(self.sql_kind(), Some(SqlErrorKind::UniqueViolation { .. }))
+    }
+
+    /// 数据库忙或被锁，可以稍后重试
+    pub fn is_busy(&self) -> bool {
+        matches!(
+            self.sql_kind(),
+            Some(SqlErrorKind::Busy | SqlErrorKind::Locked)
+        )
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound(_))
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, Error::Conflict(_))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ParseError => f.write_str("parse error"),
+            Error::SqlError(e) => e.fmt(f),
+            Error::ConfigError(e) => write!(f, "config error: {}", e),
+            Error::ArgError(e) => write!(f, "invalid argument: {}", e),
+            Error::PoolError(e) => write!(f, "pool error: {}", e),
+            Error::SchemaError(e) => write!(f, "schema error: {}", e),
+            Error::NotFound(e) => write!(f, "not found: {}", e),
+            Error::Conflict(e) => write!(f, "conflict: {}", e),
+            Error::Decode { column, expected } => {
+                write!(f, "column {} can not be decoded as {}", column, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// 语句执行出错的分类
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlErrorKind {
+    /// 违反唯一约束（包括主键），columns 为冲突的字段
+    UniqueViolation { table: String, columns: Vec<String> },
+    /// 违反外键约束
+    ForeignKeyViolation,
+    /// 非空字段写入了 NULL
+    NotNullViolation { table: String, column: String },
+    /// 违反 CHECK 等其它约束
+    ConstraintViolation,
+    /// 数据库文件被其它连接锁定（SQLITE_BUSY）
+    Busy,
+    /// 同一连接内的表被锁定（SQLITE_LOCKED）
+    Locked,
+    Other,
+}
+
+/// 语句执行出错的详细信息。sql 为出错的语句，只包含 `?` 占位符，不记录绑定的参数值
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlError {
+    pub kind: SqlErrorKind,
+    /// sqlite 的结果码，如 19 为 SQLITE_CONSTRAINT
+    pub code: Option<isize>,
+    pub message: String,
+    pub sql: Option<String>,
+}
+
+impl SqlError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            kind: SqlErrorKind::Other,
+            code: None,
+            message: message.to_string(),
+            sql: None,
+        }
+    }
+
+    /// 记录出错的语句
+    pub fn with_sql(mut self, sql: &str) -> Self {
+        self.sql = Some(sql.to_string());
+        self
+    }
+
+    /// 按结果码及错误信息分类 sqlite 的错误
+    /// # Examples
+    /// ```
+    /// use lib_sql::traits::{SqlError, SqlErrorKind};
+    ///
+    /// let e = sqlite::Error {
+    ///     code: Some(19),
+    ///     message: Some("UNIQUE constraint failed: users.username".to_string()),
+    /// };
+    /// let e = SqlError::from_sqlite(&e);
+    /// assert_eq!(
+    ///     e.kind,
+    ///     SqlErrorKind::UniqueViolation {
+    ///         table: "users".to_string(),
+    ///         columns: vec!["username".to_string()],
+    ///     }
+    /// );
+    /// ```
+    pub fn from_sqlite(e: &sqlite::Error) -> Self {
+        let message = e.message.clone().unwrap_or_else(|| e.to_string());
+        // 开启扩展结果码时低 8 位为主结果码
+        let kind = match e.code.map(|c| c & 0xff) {
+            Some(5) => SqlErrorKind::Busy,
+            Some(6) => SqlErrorKind::Locked,
+            Some(19) => constraint_kind(&message),
+            _ => SqlErrorKind::Other,
+        };
+        Self {
+            kind,
+            code: e.code,
+            message,
+            sql: None,
+        }
+    }
+}
+
+// 解析约束错误信息，如 `UNIQUE constraint failed: users.username, users.email`
+fn constraint_kind(message: &str) -> SqlErrorKind {
+    let columns = |prefix: &str| -> Option<(String, Vec<String>)> {
+        let rest = message.strip_prefix(prefix)?;
+        let mut table = String::new();
+        let mut columns = vec![];
+        for item in rest.split(',') {
+            let (t, c) = item.trim().split_once('.').unwrap_or(("", item.trim()));
+            table = t.to_string();
+            columns.push(c.to_string());
+        }
+        Some((table, columns))
+    };
+    if let Some((table, columns)) = columns("UNIQUE constraint failed: ") {
+        SqlErrorKind::UniqueViolation { table, columns }
+    } else if let Some((table, mut columns)) = columns("NOT NULL constraint failed: ") {
+        SqlErrorKind::NotNullViolation {
+            table,
+            column: columns.pop().unwrap_or_default(),
+        }
+    } else if message.starts_with("FOREIGN KEY constraint failed") {
+        SqlErrorKind::ForeignKeyViolation
+    } else {
+        SqlErrorKind::ConstraintViolation
+    }
+}
+
+impl Display for SqlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("sql error")?;
+        if let Some(code) = self.code {
+            write!(f, " (code {})", code)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(sql) = &self.sql {
+            write!(f, "; sql: {}", sql)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<SqlError> for Error {
+    fn from(e: SqlError) -> Self {
+        Error::SqlError(e)
+    }
+}
+
+impl From<sqlite::Error> for Error {
+    fn from(e: sqlite::Error) -> Self {
+        Error::SqlError(SqlError::from_sqlite(&e))
+    }
+}
+
+/// 派生 Table 的实体的生命周期钩子，使用 `#[table(hooks)]` 后由派生的 Table 调用，只需实现需要的方法
+/// # Examples
+/// ```
+/// use lib_sql::traits::{Error, Hooks, Table};
+///
+/// #[derive(Table)]
+/// #[table(name = "users", hooks)]
+/// struct User {
+///     #[id(auto_increment)]
+///     id: i32,
+///     email: String,
+///     #[column(created_at)]
+///     created_at: i64,
+///     #[column(updated_at)]
+///     updated_at: i64,
+/// }
+///
+/// impl Hooks for User {
+///     fn before_insert(&mut self) -> Result<(), Error> {
+///         self.email = self.email.trim().to_lowercase();
+///         Ok(())
+///     }
+/// }
+///
+/// let mut user = User { id: 0, email: " A@B.C ".to_string(), created_at: 0, updated_at: 0 };
+/// Table::before_insert(&mut user).unwrap(); // dao.add(user) 时调用
+/// assert_eq!(user.email, "a@b.c");
+/// assert_eq!(User::created_at_column(), Some("created_at"));
+/// ```
+pub trait Hooks {
+    /// 见 Table::before_insert
+    fn before_insert(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// 见 Table::before_update
+    fn before_update(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// 见 Table::after_load
+    fn after_load(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// 见 Table::before_delete
+    fn before_delete(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 /// 为每个表对应的结构体实现该 trait
@@ -69,6 +328,85 @@ pub trait Table: Sized {
     /// ```
     fn from_json_object(v: &JsonObject) -> Result<Self, Error>;
 
+    /// 表字段的描述信息，用于生成建表语句。默认只包含字段名与主键，不指定字段类型，
+    /// 派生 Table 时会根据结构体字段的类型生成
+    fn column_defs() -> Vec<Column> {
+        Self::columns()
+            .into_iter()
+            .map(|name| {
+                if name != Self::id() {
+                    return Column::new(name, SqlType::Any);
+                }
+                if Self::id_auto_increase() {
+                    Column::new(name, SqlType::Integer).primary_key().auto_increment()
+                } else {
+                    Column::new(name, SqlType::Any).primary_key()
+                }
+            })
+            .collect()
+    }
+
+    /// 允许客户端过滤的字段，用于 filter::SearchSpec 的白名单，默认不允许任何字段。
+    /// 派生 Table 时由 `#[column(filterable)]` 指定
+    fn filterable() -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// 允许客户端排序的字段，默认不允许任何字段。派生 Table 时由 `#[column(sortable)]` 指定
+    fn sortable() -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// 参与全文检索的文本字段，用于生成 FTS5 虚拟表，见 fts 模块，默认没有。
+    /// 派生 Table 时由 `#[column(searchable)]` 指定
+    fn searchable() -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// 软删除使用的字段，保存删除时的时间戳（秒），为 NULL 表示未删除。默认不使用软删除。
+    /// 派生 Table 时由 `#[column(soft_delete)]` 指定，字段类型应为 `Option<i64>`。
+    /// 设置后 delete 只写入删除时间，查询默认排除已删除的数据，见 Matcher::with_deleted
+    fn soft_delete_column() -> Option<&'static str> {
+        None
+    }
+
+    /// 保存创建时间的字段，添加数据时未赋值（为 0 或 NULL）则写入当前时间戳（秒）。
+    /// 派生 Table 时由 `#[column(created_at)]` 指定
+    fn created_at_column() -> Option<&'static str> {
+        None
+    }
+
+    /// 保存修改时间的字段，添加与修改数据时写入当前时间戳（秒）。派生 Table 时由 `#[column(updated_at)]` 指定
+    fn updated_at_column() -> Option<&'static str> {
+        None
+    }
+
+    /// 乐观锁使用的版本号字段，默认不使用。派生 Table 时由 `#[column(version)]` 指定，字段类型应为整数。
+    /// 设置后 set 只修改版本号与实体一致的数据，并将版本号加 1，未修改到数据时返回 Error::Conflict
+    fn version_column() -> Option<&'static str> {
+        None
+    }
+
+    /// 添加数据前调用，返回 Err 时放弃添加。派生 Table 时通过 `#[table(hooks)]` 与 Hooks 实现
+    fn before_insert(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// set 修改数据前调用，返回 Err 时放弃修改
+    fn before_update(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// 从数据库读取并转换为实体后调用
+    fn after_load(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// delete 删除实体前调用，返回 Err 时放弃删除。按主键或条件删除时没有实体，不会调用
+    fn before_delete(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// 从 Entity 转为 JsonObject，如：
     /// ```
     ///
@@ -92,20 +430,346 @@ pub trait Table: Sized {
     fn to_json_object(&self) -> Result<JsonObject, Error>;
 }
 
+/// 字段的 sql 类型，对应 sqlite 的类型亲和性
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqlType {
+    Integer,
+    Real,
+    Text,
+    Blob,
+    Numeric,
+    /// 不声明类型
+    Any,
+}
+
+impl SqlType {
+    /// 解析类型名，如 "TEXT"，不区分大小写
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_uppercase().as_str() {
+            "INTEGER" => Some(SqlType::Integer),
+            "REAL" => Some(SqlType::Real),
+            "TEXT" => Some(SqlType::Text),
+            "BLOB" => Some(SqlType::Blob),
+            "NUMERIC" => Some(SqlType::Numeric),
+            "" => Some(SqlType::Any),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SqlType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SqlType::Integer => "INTEGER",
+            SqlType::Real => "REAL",
+            SqlType::Text => "TEXT",
+            SqlType::Blob => "BLOB",
+            SqlType::Numeric => "NUMERIC",
+            SqlType::Any => "",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 表字段的描述信息
+/// # Examples
+/// ```
+/// use lib_sql::traits::{Column, SqlType};
+/// // email TEXT NOT NULL UNIQUE DEFAULT ''
+/// let column = Column::new("email", SqlType::Text).unique().default("''");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: &'static str,
+    pub sql_type: SqlType,
+    pub nullable: bool,
+    /// 默认值，为 sql 表达式，如 "0"、"''"
+    pub default: Option<&'static str>,
+    pub unique: bool,
+    pub primary_key: bool,
+    pub auto_increment: bool,
+    /// 是否为该字段创建普通索引
+    pub index: bool,
+}
+
+impl Column {
+    /// 创建一个不允许为空的字段
+    pub fn new(name: &'static str, sql_type: SqlType) -> Self {
+        Self {
+            name,
+            sql_type,
+            nullable: false,
+            default: None,
+            unique: false,
+            primary_key: false,
+            auto_increment: false,
+            index: false,
+        }
+    }
+
+    pub fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    pub fn default(mut self, value: &'static str) -> Self {
+        self.default = Some(value);
+        self
+    }
+
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    pub fn primary_key(mut self) -> Self {
+        self.primary_key = true;
+        self
+    }
+
+    pub fn auto_increment(mut self) -> Self {
+        self.auto_increment = true;
+        self
+    }
+
+    pub fn index(mut self) -> Self {
+        self.index = true;
+        self
+    }
+
+    /// 建表语句中的字段定义，如：username TEXT NOT NULL UNIQUE
+    pub fn to_sql(&self) -> String {
+        let mut sql = self.name.to_string();
+        if self.sql_type != SqlType::Any {
+            sql.push_str(&format!(" {}", self.sql_type));
+        }
+        // INTEGER PRIMARY KEY 为 rowid 的别名，不会为空
+        let rowid = self.primary_key && self.sql_type == SqlType::Integer;
+        if !self.nullable && !rowid {
+            sql.push_str(" NOT NULL");
+        }
+        if self.primary_key {
+            sql.push_str(" PRIMARY KEY");
+            if self.auto_increment {
+                sql.push_str(" AUTOINCREMENT");
+            }
+        } else if self.unique {
+            sql.push_str(" UNIQUE");
+        }
+        if let Some(default) = self.default {
+            sql.push_str(&format!(" DEFAULT {}", default));
+        }
+        sql
+    }
+}
+
+/// 事务的加锁方式，对应 sqlite 的 BEGIN DEFERRED / IMMEDIATE / EXCLUSIVE
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TransactionMode {
+    /// 首次读写时才加锁
+    #[default]
+    Deferred,
+    /// 开始时即获取写锁，适合先查后写的事务，避免升级写锁时出现 busy
+    Immediate,
+    /// 开始时即获取排它锁
+    Exclusive,
+}
+
 /// 为不同数据库提供通用的接口
 /**
  * list: 按指定条件查询
  * list_all: 查询全表
- * set: 修改数据
+ * set: 修改数据，使用版本号时检查乐观锁
  *  delete: 删除数据
  *  add: 添加数据
+ *  insert: 添加数据并返回生成的主键
+ *  insert_and_reload: 添加数据并重新读取
+ *  add_many: 批量添加数据
+ *  get: 按主键查询
+ *  find_one: 按条件查询一条
+ *  exists: 判断是否存在满足条件的数据
+ *  count: 按条件统计
+ *  delete_by_id: 按主键删除
+ *  delete_where: 按条件删除
+ *  upsert: 添加数据，冲突时更新
+ *  update_where: 按条件更新指定字段
+ *  aggregate: 统计查询，返回 JsonObject
+ *  load_related: 批量读取关联的数据
+ *  restore: 恢复软删除的数据
+ *  purge: 按主键物理删除
+ *  transaction: 在事务中执行
  */
 pub trait CommInterface {
     fn list<T: Table>(&self, search_arg: &mut Search) -> Result<Vec<T>, Error>;
     fn list_all<T: Table>(&self) -> Result<Vec<T>, Error>;
+    /// 按主键修改数据，返回受影响的行数。使用软删除时不修改已删除的数据，也不修改删除时间。
+    /// 使用版本号（Table::version_column）时只修改版本号与实体一致的数据并将版本号加 1，
+    /// 数据已被修改或删除时返回 Error::Conflict
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// // update users set ...,version=version+1 where id=? and version=?
+    /// // match dao.set(user) {
+    /// //     Err(e) if e.is_conflict() => { /* 重新读取后再修改 */ }
+    /// //     ...
+    /// // }
+    /// ```
     fn set<T: Table>(&self, entity: T) -> Result<usize, Error>;
     fn delete<T: Table>(&self, entity: T) -> Result<usize, Error>;
     fn add<T: Table>(&self, entity: T) -> Result<usize, Error>;
+
+    /// 添加数据，返回新行的 rowid，自增主键的表即为生成的主键
+    fn insert<T: Table>(&self, entity: T) -> Result<i64, Error>;
+
+    /// 添加数据后按 rowid 重新读取，可以拿到自增主键与数据库填充的默认值
+    fn insert_and_reload<T: Table>(&self, entity: T) -> Result<T, Error>;
+
+    /// 在一个事务中使用同一条预编译语句批量添加，返回每一行的 rowid。
+    /// 插入的字段以第一个实体为准，其余实体缺少的字段按 NULL 写入
+    fn add_many<T: Table>(&self, entities: Vec<T>) -> Result<Vec<i64>, Error>;
+
+    /// 按主键查询，不存在时返回 None
+    fn get<T: Table, I: ToType>(&self, id: I) -> Result<Option<T>, Error>;
+
+    /// 按条件查询第一条，条件为空时取表中任意一条
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    /// use lib_sql::utils::{Matcher, Operator};
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// let mut matcher = Matcher::new();
+    /// matcher.and("username", Operator::Eq, "demo");
+    /// // let user = dao.find_one::<User>(&matcher)?;
+    /// ```
+    fn find_one<T: Table>(&self, matcher: &Matcher) -> Result<Option<T>, Error>;
+
+    /// 是否存在满足条件的数据
+    fn exists<T: Table>(&self, matcher: &Matcher) -> Result<bool, Error>;
+
+    /// 满足条件的数据条数，条件为空时统计全表
+    fn count<T: Table>(&self, matcher: &Matcher) -> Result<i64, Error>;
+
+    /// 按主键删除，返回删除的行数
+    fn delete_by_id<T: Table, I: ToType>(&self, id: I) -> Result<usize, Error>;
+
+    /// 按条件删除，返回删除的行数。条件为空时返回 ArgError，避免误删全表
+    fn delete_where<T: Table>(&self, matcher: &Matcher) -> Result<usize, Error>;
+
+    /// 恢复软删除的数据，返回恢复的行数。表未使用软删除时返回 ArgError
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// // dao.delete_by_id::<User, _>(1)?; // update users set deleted_at=? where id=? and deleted_at is null
+    /// // dao.restore::<User, _>(1)?;      // update users set deleted_at=null where id=?
+    /// ```
+    fn restore<T: Table, I: ToType>(&self, id: I) -> Result<usize, Error>;
+
+    /// 按主键物理删除，不论是否已软删除，返回删除的行数
+    fn purge<T: Table, I: ToType>(&self, id: I) -> Result<usize, Error>;
+
+    /// 添加数据，conflict_columns 上发生唯一冲突时更新其余字段并将版本号加 1，返回受影响的行数。
+    /// conflict_columns 为空时使用主键
+    ///
+    /// 与 set 相同，冲突的数据已软删除时不修改，也不修改删除时间；
+    /// 使用乐观锁时版本号与数据库中的不一致，或冲突的数据已软删除，返回 [`Error::Conflict`]
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// // insert into users (...) values (...) on conflict(username) do update set email=excluded.email, ...
+    /// // dao.upsert(user, &["username"])?;
+    /// ```
+    fn upsert<T: Table>(&self, entity: T, conflict_columns: &[&str]) -> Result<usize, Error>;
+
+    /// 只更新 values 中提供的字段，作用于所有满足条件的数据，返回受影响的行数。
+    /// 使用版本号时 values 中未提供版本号则将版本号加 1，不检查乐观锁。
+    /// 条件为空或字段不属于该表时返回 ArgError
+    /// # Examples
+    /// ```no_run
+    /// use lib_json::object::JsonObject;
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    /// use lib_sql::utils::{Matcher, Operator};
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// let mut matcher = Matcher::new();
+    /// matcher.and("id", Operator::Eq, 1);
+    /// let mut values = JsonObject::new();
+    /// values.set_str("password", "...");
+    /// // dao.update_where::<User>(&matcher, values)?;
+    /// ```
+    fn update_where<T: Table>(&self, matcher: &Matcher, values: JsonObject) -> Result<usize, Error>;
+
+    /// 按 search 的 fileds、group、matcher、sort 查询，每一行以列名为 key 转为 JsonObject，
+    /// 值保持数据库中的类型，适用于 count、sum 等无法映射为实体的查询。不统计总数
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    /// use lib_sql::utils::Search;
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// let mut search = Search::new();
+    /// search.fileds = "status, count(*) as cnt".to_string();
+    /// search.group = "status".to_string();
+    /// // let rows = dao.aggregate::<User>(&search)?;
+    /// // rows[0].get_i64("cnt")
+    /// ```
+    fn aggregate<T: Table>(&self, search: &Search) -> Result<Vec<JsonObject>, Error>;
+
+    /// 批量读取 parents 关联的 C，只执行一次查询，结果与 parents 按顺序一一对应，
+    /// 每组按 C 的主键排序，排除已软删除的数据。关联字段不属于对应的表时返回 ArgError
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::relation::Relation;
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// // select * from sessions where ((sessions.user_id in (?,?))) order by sessions.id
+    /// // let sessions = dao.load_related::<User, Session>(&users, &Relation::has_many("user_id"))?;
+    /// // for (user, sessions) in users.iter().zip(sessions) { ... }
+    /// ```
+    fn load_related<P: Table, C: Table>(
+        &self,
+        parents: &[P],
+        relation: &Relation,
+    ) -> Result<Vec<Vec<C>>, Error>;
+
+    /// 在事务中执行 f，返回 Ok 时提交，返回 Err 或 panic 时回滚。
+    /// 在事务中再次调用时使用 savepoint 实现嵌套事务，只回滚内层的修改
+    /// # Examples
+    /// ```no_run
+    /// use lib_sql::sources::Dao;
+    /// use lib_sql::traits::CommInterface;
+    ///
+    /// let dao = Dao::new().unwrap();
+    /// let count = dao.transaction(|tx| {
+    ///     // tx.add(user)?;
+    ///     Ok(1)
+    /// });
+    /// ```
+    fn transaction<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&Self) -> Result<R, Error>,
+    {
+        self.transaction_with(TransactionMode::Deferred, f)
+    }
+
+    /// 同 transaction，可指定最外层事务的加锁方式，嵌套事务忽略该参数
+    fn transaction_with<R, F>(&self, mode: TransactionMode, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&Self) -> Result<R, Error>;
 }
 
 /// 区分不同数据库的连接，为每个数据库实现该 trait
@@ -0,0 +1,454 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+};
+
+use lib_json::{list::JsonList, object::JsonObject, types::Type};
+use sqlite::Connection;
+
+use crate::{
+    interface::{insert_columns, insert_object, insert_query},
+    sources::Dao,
+    traits::{Column, CommInterface, Error, SqlType, Table},
+    utils::{Search, now},
+};
+
+/// 导入导出的数据格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// 对象数组，每个对象一行
+    Json,
+    /// 每行一个对象，即 JSON Lines
+    JsonLines,
+    /// 第一行为字段名，空字段为 NULL，空字符串输出为 ""
+    Csv,
+}
+
+impl Format {
+    /// 解析格式名：json、jsonl（或 ndjson）、csv，不区分大小写
+    /// # Examples
+    /// ```
+    /// use lib_sql::transfer::Format;
+    ///
+    /// assert_eq!(Format::parse("JSONL"), Some(Format::JsonLines));
+    /// assert_eq!(Format::from_path("./users.csv"), Some(Format::Csv));
+    /// assert_eq!(Format::parse("xml"), None);
+    /// ```
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "jsonl" | "ndjson" => Some(Format::JsonLines),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    /// 按文件的扩展名确定格式
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        Self::parse(&path.as_ref().extension()?.to_string_lossy())
+    }
+}
+
+/// 导入的选项
+/// # Examples
+/// ```no_run
+/// use lib_sql::sources::Dao;
+/// use lib_sql::transfer::{Format, ImportOptions};
+///
+/// let dao = Dao::new().unwrap();
+/// // 文件中的 mail 对应表的 email，不导入 id，由数据库重新生成
+/// let options = ImportOptions::new().map("mail", "email").skip("id").dry_run(true);
+/// // let report = dao.import::<User>(&data, Format::Csv, &options)?;
+/// // for e in &report.errors { println!("{}", e); }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    mapping: HashMap<String, String>,
+    skip: HashSet<String>,
+    dry_run: bool,
+}
+
+impl ImportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 数据中的 field 导入到表的 column，未映射的字段按同名字段导入
+    pub fn map(mut self, field: &str, column: &str) -> Self {
+        self.mapping.insert(field.to_string(), column.to_string());
+        self
+    }
+
+    /// 忽略数据中的 field，数据中有表中不存在的字段时需要忽略，否则该行报错
+    pub fn skip(mut self, field: &str) -> Self {
+        self.skip.insert(field.to_string());
+        self
+    }
+
+    /// 只检查数据，在事务中导入后回滚，报告每行的错误，不写入数据库
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// 导入时某一行的错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    /// 第几条数据，从 1 开始，不包括 csv 的标题行与空行
+    pub row: usize,
+    pub message: String,
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "row {}: {}", self.row, self.message)
+    }
+}
+
+/// 导入的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    /// 数据的总条数
+    pub total: usize,
+    /// 导入成功的条数，未提交时为试导入成功的条数
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+    /// 是否已写入数据库，有错误或 dry_run 时回滚，不写入任何数据
+    pub committed: bool,
+}
+
+impl ImportReport {
+    /// 所有数据均可导入
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+// 为 sqlite 实现导入导出相关的接口
+impl Dao<Connection> {
+    /// 按 search 查询 T 并导出为 format 格式的文本，字段顺序与 Table::columns 一致，
+    /// 默认排除已软删除的数据，导出全部数据时使用 Search::new()
+    pub fn export<T: Table>(&self, search: &Search, format: Format) -> Result<String, Error> {
+        let mut rows = vec![];
+        for entity in self.list::<T>(&mut search.clone())? {
+            let object = entity.to_json_object()?;
+            let mut row = JsonObject::new();
+            for column in T::columns() {
+                let value = object.get_data(column).cloned().unwrap_or(Type::Null);
+                row.set_data(column, value);
+            }
+            rows.push(row);
+        }
+
+        let mut out = String::new();
+        match format {
+            Format::Json => {
+                let rows: Vec<String> = rows.iter().map(|r| r.to_json()).collect();
+                if rows.is_empty() {
+                    out.push_str("[]\n");
+                } else {
+                    out.push_str(&format!("[\n{}\n]\n", rows.join(",\n")));
+                }
+            }
+            Format::JsonLines => {
+                for row in &rows {
+                    out.push_str(&row.to_json());
+                    out.push('\n');
+                }
+            }
+            Format::Csv => {
+                out.push_str(&T::columns().join(","));
+                out.push('\n');
+                for row in &rows {
+                    let fields: Vec<String> = T::columns()
+                        .iter()
+                        .map(|c| csv_field(row.get_data(c).unwrap_or(&Type::Null)))
+                        .collect();
+                    out.push_str(&fields.join(","));
+                    out.push('\n');
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// 导入 format 格式的文本到 T，字段按 options 映射后按表中声明的类型转换，如 csv 中的 "18" 转为整数。
+    /// 经过实体的转换与 before_insert，并写入创建与修改时间。数据中有主键时保留主键，否则由数据库生成。
+    /// 所有数据在一个事务中导入，有任一行出错时回滚，返回的报告中包含每行的错误；
+    /// 数据整体无法解析或映射到不存在的字段时返回 ArgError
+    pub fn import<T: Table>(
+        &self,
+        data: &str,
+        format: Format,
+        options: &ImportOptions,
+    ) -> Result<ImportReport, Error> {
+        let defs = T::column_defs();
+        for column in options.mapping.values() {
+            if !defs.iter().any(|d| d.name == column) {
+                return Err(Error::ArgError(format!(
+                    "column {} does not exist in {}",
+                    column,
+                    T::table_name()
+                )));
+            }
+        }
+        let records = parse_records(data, format)?;
+
+        let mut report = ImportReport {
+            total: records.len(),
+            ..Default::default()
+        };
+        let mut rollback = false;
+        let result = self.transaction(|tx| {
+            for (i, record) in records.iter().enumerate() {
+                let result = match record {
+                    Ok(record) => tx.import_row::<T>(record, options, &defs),
+                    Err(message) => Err(message.clone()),
+                };
+                match result {
+                    Ok(_) => report.imported += 1,
+                    Err(message) => report.errors.push(RowError { row: i + 1, message }),
+                }
+            }
+            if report.errors.is_empty() && !options.dry_run {
+                return Ok(());
+            }
+            rollback = true;
+            Err(Error::ArgError("import rolled back".to_string()))
+        });
+        match result {
+            Ok(_) => report.committed = true,
+            Err(_) if rollback => {}
+            Err(e) => return Err(e),
+        }
+        Ok(report)
+    }
+
+    // 导入一行数据，出错时返回错误信息
+    fn import_row<T: Table>(
+        &self,
+        record: &JsonObject,
+        options: &ImportOptions,
+        defs: &[Column],
+    ) -> Result<(), String> {
+        let mut row = JsonObject::new();
+        for field in record.keys() {
+            if options.skip.contains(field) {
+                continue;
+            }
+            let column = options.mapping.get(field).map_or(field, |c| c.as_str());
+            let Some(def) = defs.iter().find(|d| d.name == column) else {
+                return Err(format!("unknown column {}", field));
+            };
+            let value = record.get_data(field).unwrap_or(&Type::Null);
+            let value = coerce(value, def.sql_type)
+                .ok_or_else(|| format!("column {} expected {}, got {}", column, def.sql_type, value))?;
+            row.remove(column);
+            row.set_data(column, value);
+        }
+
+        // 自增主键、时间与版本号由数据库或实体的转换生成，数据中没有时先填 0
+        let has_id = !matches!(row.get_data(T::id()), None | Some(Type::Null));
+        let generated = [
+            (T::id_auto_increase() && !has_id).then(T::id),
+            T::created_at_column(),
+            T::updated_at_column(),
+            T::version_column(),
+        ];
+        for column in generated.into_iter().flatten() {
+            if matches!(row.get_data(column), None | Some(Type::Null)) {
+                row.remove(column);
+                row.set_i64(column, 0);
+            }
+        }
+
+        let entity = T::from_json_object(&row).map_err(|e| e.to_string())?;
+        let object = insert_object(entity, now()).map_err(|e| e.to_string())?;
+        let mut columns = insert_columns::<T>(&object);
+        if has_id && T::id_auto_increase() {
+            columns.insert(0, T::id());
+        }
+        self.execute(&insert_query::<T>(&columns, &object))
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// 将数据解析为记录，单条记录无法解析时作为该行的错误
+pub(crate) fn parse_records(data: &str, format: Format) -> Result<Vec<Result<JsonObject, String>>, Error> {
+    match format {
+        Format::Json => {
+            let list: JsonList = data
+                .trim()
+                .parse()
+                .map_err(|_| Error::ArgError("invalid json array".to_string()))?;
+            Ok(list
+                .0
+                .into_iter()
+                .map(|v| match v {
+                    Type::JsonObject(o) => Ok(o),
+                    v => Err(format!("expected an object, got {}", v)),
+                })
+                .collect())
+        }
+        Format::JsonLines => Ok(data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                line.parse::<JsonObject>()
+                    .map_err(|_| format!("invalid json: {}", line))
+            })
+            .collect()),
+        Format::Csv => {
+            let mut lines = parse_csv(data)?.into_iter();
+            let Some(header) = lines.next() else {
+                return Ok(vec![]);
+            };
+            Ok(lines
+                .map(|fields| {
+                    if fields.len() != header.len() {
+                        return Err(format!(
+                            "expected {} fields, got {}",
+                            header.len(),
+                            fields.len()
+                        ));
+                    }
+                    let mut record = JsonObject::new();
+                    for ((name, _), (value, quoted)) in header.iter().zip(fields) {
+                        if value.is_empty() && !quoted {
+                            record.set_null(name);
+                        } else {
+                            record.set_str(name, &value);
+                        }
+                    }
+                    Ok(record)
+                })
+                .collect())
+        }
+    }
+}
+
+// 解析 csv，字段中的逗号、换行与引号需要用双引号括起，引号写为两个引号。
+// 返回每行的字段及字段是否带引号，跳过空行
+fn parse_csv(data: &str) -> Result<Vec<Vec<(String, bool)>>, Error> {
+    let mut rows = vec![];
+    let mut fields = vec![];
+    let (mut field, mut quoted, mut in_quote) = (String::new(), false, false);
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quote {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quote = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() && !quoted => (quoted, in_quote) = (true, true),
+            ',' => fields.push((std::mem::take(&mut field), std::mem::take(&mut quoted))),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push((std::mem::take(&mut field), std::mem::take(&mut quoted)));
+                rows.push(std::mem::take(&mut fields));
+            }
+            c => field.push(c),
+        }
+    }
+    if in_quote {
+        return Err(Error::ArgError("unterminated quote in csv".to_string()));
+    }
+    if !field.is_empty() || quoted || !fields.is_empty() {
+        fields.push((field, quoted));
+        rows.push(fields);
+    }
+    rows.retain(|fields| !(fields.len() == 1 && fields[0] == (String::new(), false)));
+    Ok(rows)
+}
+
+// csv 的字段，NULL 为空，包含特殊字符的文本及空字符串加上引号
+fn csv_field(value: &Type) -> String {
+    let text = match value {
+        Type::Null => return String::new(),
+        Type::String(s) => s.clone(),
+        Type::JsonObject(o) => o.to_json(),
+        Type::JsonList(l) => l.to_json(),
+        v => return v.to_string(),
+    };
+    if text.is_empty() || text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+// 按字段声明的类型转换值，无法转换时返回 None
+fn coerce(value: &Type, sql_type: SqlType) -> Option<Type> {
+    if let Type::Null = value {
+        return Some(Type::Null);
+    }
+    // 数字字段的空文本视为 NULL，如 csv 中带引号的空字段
+    if let Type::String(s) = value
+        && s.trim().is_empty()
+        && matches!(sql_type, SqlType::Integer | SqlType::Real)
+    {
+        return Some(Type::Null);
+    }
+    match sql_type {
+        SqlType::Integer => match value {
+            Type::String(s) => match s.trim() {
+                "true" => Some(Type::I64(1)),
+                "false" => Some(Type::I64(0)),
+                s => s.parse().ok().map(Type::I64),
+            },
+            Type::Boolean(b) => Some(Type::I64(*b as i64)),
+            Type::F32(_) | Type::F64(_) => {
+                let f = as_f64(value)?;
+                (f.fract() == 0.0).then_some(Type::I64(f as i64))
+            }
+            v => as_i64(v).map(Type::I64),
+        },
+        SqlType::Real => match value {
+            Type::String(s) => s.trim().parse().ok().map(Type::F64),
+            v => as_f64(v).map(Type::F64),
+        },
+        SqlType::Text => match value {
+            Type::String(_) => Some(value.clone()),
+            Type::JsonObject(o) => Some(Type::String(o.to_json())),
+            Type::JsonList(l) => Some(Type::String(l.to_json())),
+            v => Some(Type::String(v.to_string())),
+        },
+        SqlType::Blob | SqlType::Numeric | SqlType::Any => Some(value.clone()),
+    }
+}
+
+fn as_i64(value: &Type) -> Option<i64> {
+    match *value {
+        Type::I8(v) => Some(v as i64),
+        Type::I16(v) => Some(v as i64),
+        Type::I32(v) => Some(v as i64),
+        Type::I64(v) => Some(v),
+        Type::I128(v) => i64::try_from(v).ok(),
+        Type::ISIZE(v) => Some(v as i64),
+        Type::U8(v) => Some(v as i64),
+        Type::U16(v) => Some(v as i64),
+        Type::U32(v) => Some(v as i64),
+        Type::U64(v) => i64::try_from(v).ok(),
+        Type::U128(v) => i64::try_from(v).ok(),
+        Type::USIZE(v) => i64::try_from(v).ok(),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Type) -> Option<f64> {
+    match *value {
+        Type::F32(v) => Some(v as f64),
+        Type::F64(v) => Some(v),
+        _ => as_i64(value).map(|v| v as f64),
+    }
+}
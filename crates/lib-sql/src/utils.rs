@@ -1,15 +1,62 @@
+use lib_json::{list::JsonList, types::Type};
 use serde::Deserialize;
 
-use crate::traits::Error;
-use std::fs;
+use crate::{
+    backup::BackupConfig,
+    observer::QueryLogConfig,
+    relation::Relation,
+    schema::SchemaCheckMode,
+    traits::{Error, Table},
+};
+use std::{
+    fmt::Display,
+    fs,
+    ops::Not,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 /// 配置文件
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize)]
 pub struct Config {
     pub datasource: String,
     pub webdir: Option<String>,
     pub port: Option<u16>,
+    pub pool: Option<PoolConfig>,
+    /// 启动时表结构检查的方式：strict、warn 或 off，默认 strict
+    pub schema_check: Option<SchemaCheckMode>,
+    /// 语句日志，对应配置文件中的 [query_log]
+    pub query_log: Option<QueryLogConfig>,
+    /// 备份，对应配置文件中的 [backup]
+    pub backup: Option<BackupConfig>,
+}
+
+/// sqlite 内存数据库的数据源
+pub const MEMORY: &str = ":memory:";
+
+impl Config {
+    /// 只指定数据源的配置，其它均为默认值
+    pub fn new(datasource: &str) -> Self {
+        Self {
+            datasource: datasource.to_string(),
+            ..Default::default()
+        }
+    }
 
+    /// 内存数据库的配置，用于测试，见 Dao::memory 与 Pool::new
+    pub fn memory() -> Self {
+        Self::new(MEMORY)
+    }
+}
+
+/// 连接池配置，对应配置文件中的 [pool]
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct PoolConfig {
+    /// 最大连接数，默认 8
+    pub max_size: Option<usize>,
+    /// 获取连接的超时时间，单位毫秒，默认 3000
+    pub timeout: Option<u64>,
+    /// 每个新连接创建后执行的 pragma，如 "journal_mode = WAL"
+    pub pragmas: Option<Vec<String>>,
 }
 
 /// 读取指定路径的配置文件，配置文件使用 toml 进行解析
@@ -26,13 +73,177 @@ pub fn read_config(path: &str) -> Result<Config, Error> {
     if let Err(e) = config {
         return Err(Error::ConfigError(format!("parse config {:?}", e)));
     }
-    let config: Config = config.unwrap();
-    println!("{:?}", config);
-    Ok(config)
+    Ok(config.unwrap())
+}
+
+/// 当前的 unix 时间戳，单位秒
+pub(crate) fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// sql 语句及按顺序绑定的参数，语句中的参数使用 `?` 占位
+/// # Examples
+/// ```
+/// use lib_sql::utils::Query;
+/// let mut query = Query::new("select * from animals where name=?");
+/// query.bind("it's a cat");
+/// assert_eq!(query.params.len(), 1);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Query {
+    pub sql: String,
+    pub params: Vec<Type>,
+}
+
+impl Query {
+    pub fn new(sql: &str) -> Self {
+        Self {
+            sql: sql.to_string(),
+            params: vec![],
+        }
+    }
+
+    /// 使用语句及参数创建
+    pub fn with_params(sql: &str, params: Vec<Type>) -> Self {
+        Self {
+            sql: sql.to_string(),
+            params,
+        }
+    }
+
+    /// 判断语句是否为空
+    pub fn is_empty(&self) -> bool {
+        self.sql.is_empty()
+    }
+
+    /// 追加语句片段
+    pub fn push_sql(&mut self, sql: &str) -> &mut Self {
+        self.sql.push_str(sql);
+        self
+    }
+
+    /// 追加一个绑定参数，参数顺序需要与语句中 `?` 的顺序一致
+    pub fn bind<T: ToType>(&mut self, value: T) -> &mut Self {
+        self.params.push(value.to_type());
+        self
+    }
+
+    /// 追加另一个语句片段及其参数
+    pub fn append(&mut self, other: Query) -> &mut Self {
+        self.sql.push_str(&other.sql);
+        self.params.extend(other.params);
+        self
+    }
+
+    /// 使用分隔符拼接多个语句片段，如：`(a=?) and (b=?)`
+    pub fn join(list: &[Query], sep: &str) -> Query {
+        let mut query = Query::default();
+        for (i, item) in list.iter().enumerate() {
+            if i > 0 {
+                query.sql.push_str(sep);
+            }
+            query.append(item.clone());
+        }
+        query
+    }
+}
+
+/// 可作为 sql 参数绑定的值，转换为 lib_json 的 Type
+pub trait ToType {
+    fn to_type(&self) -> Type;
+}
+
+macro_rules! impl_to_type {
+    ($($t:ty => $v:ident),* $(,)?) => {
+        $(
+            impl ToType for $t {
+                fn to_type(&self) -> Type {
+                    Type::$v(*self)
+                }
+            }
+        )*
+    };
+}
+
+impl_to_type!(
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64, i128 => I128, isize => ISIZE,
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64, u128 => U128, usize => USIZE,
+    f32 => F32, f64 => F64, bool => Boolean,
+);
+
+impl ToType for str {
+    fn to_type(&self) -> Type {
+        Type::String(self.to_string())
+    }
+}
+
+impl ToType for String {
+    fn to_type(&self) -> Type {
+        Type::String(self.clone())
+    }
+}
+
+impl ToType for Type {
+    fn to_type(&self) -> Type {
+        self.clone()
+    }
 }
 
-/// sql 条件操作符，包含 不等于、等于、大于、大于等于、小于、小于等于、包含、不包含、模糊包含、模糊不包含、位运算等于、位运算不等于
-#[derive(Debug)]
+impl<T: ToType> ToType for Option<T> {
+    fn to_type(&self) -> Type {
+        match self {
+            Some(v) => v.to_type(),
+            None => Type::Null,
+        }
+    }
+}
+
+/// 数组转为 JsonList，用于 in / not in 条件
+impl<T: ToType> ToType for [T] {
+    fn to_type(&self) -> Type {
+        Type::JsonList(JsonList::from_vec(self.iter().map(|v| v.to_type()).collect()))
+    }
+}
+
+impl<T: ToType, const N: usize> ToType for [T; N] {
+    fn to_type(&self) -> Type {
+        self.as_slice().to_type()
+    }
+}
+
+/// 空值，用于 is null / is not null 等不需要数据值的条件
+impl ToType for () {
+    fn to_type(&self) -> Type {
+        Type::Null
+    }
+}
+
+impl<T: ToType> ToType for Vec<T> {
+    fn to_type(&self) -> Type {
+        self.as_slice().to_type()
+    }
+}
+
+impl<T: ToType + ?Sized> ToType for &T {
+    fn to_type(&self) -> Type {
+        (**self).to_type()
+    }
+}
+
+/// 取出值的文本内容，字符串不带引号
+pub(crate) fn type_text(value: &Type) -> String {
+    match value {
+        Type::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// sql 条件操作符，包含 不等于、等于、大于、大于等于、小于、小于等于、包含、不包含、模糊包含、模糊不包含、位运算等于、位运算不等于，
+/// 以及 为空、不为空、区间、glob 匹配、前缀匹配、后缀匹配、忽略大小写等于、全文检索
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
     Ne,
     Eq,
@@ -46,93 +257,256 @@ pub enum Operator {
     LandNe,
     Like,
     NotLike,
+    /// is null，忽略数据值
+    IsNull,
+    /// is not null，忽略数据值
+    IsNotNull,
+    /// between ? and ?，数据值为两个元素的数组，如 `[1, 10]`
+    Between,
+    /// not between ? and ?，数据值同 Between
+    NotBetween,
+    /// glob 匹配，区分大小写，通配符为 `*` 与 `?`
+    Glob,
+    /// 前缀匹配，like 'value%'
+    StartsWith,
+    /// 后缀匹配，like '%value'
+    EndsWith,
+    /// 忽略大小写的等于，= ? collate nocase
+    EqNoCase,
+    /// FTS5 全文检索，字段为表名，数据值为 FTS5 查询语句，需要先建立全文索引，见 fts 模块
+    Match,
 }
 
-impl ToString for Operator {
-    fn to_string(&self) -> String {
-        match self {
-            Operator::Ne => String::from("<>"),
-            Operator::Eq => String::from("="),
-            Operator::Ge => String::from(">="),
-            Operator::Gt => String::from(">"),
-            Operator::Le => String::from("<="),
-            Operator::Lt => String::from("<"),
-            Operator::In => String::from("in"),
-            Operator::NotIn => String::from("not in"),
-            Operator::Land => String::from("&"),
-            Operator::LandNe => String::from("&<>"),
-            Operator::Like => String::from("like"),
-            Operator::NotLike => String::from("not like"),
-        }
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Operator::Ne => "<>",
+            Operator::Eq => "=",
+            Operator::Ge => ">=",
+            Operator::Gt => ">",
+            Operator::Le => "<=",
+            Operator::Lt => "<",
+            Operator::In => "in",
+            Operator::NotIn => "not in",
+            Operator::Land => "&",
+            Operator::LandNe => "&<>",
+            Operator::Like => "like",
+            Operator::NotLike => "not like",
+            Operator::IsNull => "is null",
+            Operator::IsNotNull => "is not null",
+            Operator::Between => "between",
+            Operator::NotBetween => "not between",
+            Operator::Glob => "glob",
+            Operator::StartsWith => "like",
+            Operator::EndsWith => "like",
+            Operator::EqNoCase => "=",
+            Operator::Match => "match",
+        };
+        f.write_str(s)
     }
 }
 
 /// 基础条件，记录对应的字段、运算符以及数据值
-#[derive(Debug)]
-pub struct Cond<T: ToString> {
+#[derive(Debug, Clone)]
+pub struct Cond {
     key: String,
     op: Operator,
-    value: T,
+    value: Type,
 }
 
-impl<T: ToString> Cond<T> {
-    pub fn new(key: &str, op: Operator, value: T) -> Self {
+impl Cond {
+    pub fn new<T: ToType>(key: &str, op: Operator, value: T) -> Self {
         Self {
             key: key.to_string(),
             op,
-            value,
+            value: value.to_type(),
         }
     }
 
-    /// 解析最终的 sql 条件
+    /// 解析最终的 sql 条件，数据值均以参数形式绑定
     /// # Examples
     /// ```
     /// use lib_sql::utils::Operator;
     /// use lib_sql::utils::Cond;
-    /// // id=1
+    /// // id=?
     /// let cond = Cond::new("id", Operator::Eq, 1);
     /// ```
-    fn parse(&self) -> String {
+    fn parse(&self) -> Query {
+        let value = self.value.clone();
         match self.op {
             Operator::Ne
             | Operator::Eq
             | Operator::Ge
             | Operator::Gt
             | Operator::Le
-            | Operator::Lt => format!(
-                "({} {} {})",
-                self.key,
-                self.op.to_string(),
-                self.value.to_string()
+            | Operator::Lt => Query::with_params(&format!("({} {} ?)", self.key, self.op), vec![value]),
+            Operator::In | Operator::NotIn => {
+                let params = match value {
+                    Type::JsonList(list) => list.iter().cloned().collect(),
+                    _ => vec![value],
+                };
+                let holders = vec!["?"; params.len()].join(",");
+                Query::with_params(&format!("({} {} ({}))", self.key, self.op, holders), params)
+            }
+            // (key & v) = v 等价于 (~key & v) = 0，数据值只需绑定一次
+            Operator::Land => {
+                Query::with_params(&format!("((~{} & ?) = 0)", self.key), vec![value])
+            }
+            Operator::LandNe => {
+                Query::with_params(&format!("((~{} & ?) <> 0)", self.key), vec![value])
+            }
+            Operator::Like | Operator::NotLike => Query::with_params(
+                &format!("({} {} ?)", self.key, self.op),
+                vec![Type::String(format!("%{}%", type_text(&value)))],
             ),
-            Operator::In | Operator::NotIn => format!(
-                "({} {} ({}))",
-                self.key,
-                self.op.to_string(),
-                self.value.to_string()
+            Operator::StartsWith => Query::with_params(
+                &format!("({} like ?)", self.key),
+                vec![Type::String(format!("{}%", type_text(&value)))],
             ),
-            Operator::Land | Operator::LandNe => format!(
-                "({} {} {} {})",
-                self.key,
-                self.op.to_string(),
-                self.value.to_string(),
-                self.value.to_string()
+            Operator::EndsWith => Query::with_params(
+                &format!("({} like ?)", self.key),
+                vec![Type::String(format!("%{}", type_text(&value)))],
             ),
-            Operator::Like | Operator::NotLike => format!(
-                "({} {} \"%{}%\")",
-                self.key,
-                self.op.to_string(),
-                self.value.to_string()
+            Operator::IsNull | Operator::IsNotNull => {
+                Query::new(&format!("({} {})", self.key, self.op))
+            }
+            // 数据值不足两个时以 null 补齐，此时不会匹配任何数据
+            Operator::Between | Operator::NotBetween => {
+                let mut params: Vec<Type> = match value {
+                    Type::JsonList(list) => list.iter().take(2).cloned().collect(),
+                    _ => vec![value],
+                };
+                params.resize(2, Type::Null);
+                Query::with_params(&format!("({} {} ? and ?)", self.key, self.op), params)
+            }
+            Operator::Glob => {
+                Query::with_params(&format!("({} glob ?)", self.key), vec![value])
+            }
+            Operator::EqNoCase => {
+                Query::with_params(&format!("({} = ? collate nocase)", self.key), vec![value])
+            }
+            Operator::Match => Query::with_params(
+                &format!(
+                    "(rowid in (select rowid from {0}_fts where {0}_fts match ?))",
+                    self.key
+                ),
+                vec![value],
             ),
         }
     }
 }
 
+/// 条件树，支持 and、or、not 任意嵌套，解析时为每一层加上括号
+/// # Examples
+/// ```
+/// use lib_sql::utils::{Condition, Matcher, Operator};
+///
+/// // (name = ?) and ((age < ?) or (not (owner is null)))
+/// let cond = Condition::new("name", Operator::Eq, "cat").and(
+///     Condition::new("age", Operator::Lt, 3)
+///         .or(!Condition::new("owner", Operator::IsNull, ())),
+/// );
+/// let mut matcher = Matcher::new();
+/// matcher.and_cond(cond);
+/// ```
+#[derive(Debug, Clone)]
+pub enum Condition {
+    Cond(Cond),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    /// 单个条件
+    pub fn new<T: ToType>(key: &str, op: Operator, value: T) -> Self {
+        Condition::Cond(Cond::new(key, op, value))
+    }
+
+    /// 所有条件都满足，为空时不产生条件
+    pub fn all(list: Vec<Condition>) -> Self {
+        Condition::All(list)
+    }
+
+    /// 任意条件满足，为空时不产生条件
+    pub fn any(list: Vec<Condition>) -> Self {
+        Condition::Any(list)
+    }
+
+    /// 与另一个条件组合为 and，连续调用时合并为同一层
+    pub fn and(self, other: Condition) -> Self {
+        match self {
+            Condition::All(mut list) => {
+                list.push(other);
+                Condition::All(list)
+            }
+            cond => Condition::All(vec![cond, other]),
+        }
+    }
+
+    /// 与另一个条件组合为 or，连续调用时合并为同一层
+    pub fn or(self, other: Condition) -> Self {
+        match self {
+            Condition::Any(mut list) => {
+                list.push(other);
+                Condition::Any(list)
+            }
+            cond => Condition::Any(vec![cond, other]),
+        }
+    }
+
+    /// 解析为 sql 条件及参数，没有任何条件时返回空语句
+    pub(crate) fn parse(&self) -> Query {
+        match self {
+            Condition::Cond(cond) => cond.parse(),
+            Condition::All(list) => Self::parse_group(list, " and "),
+            Condition::Any(list) => Self::parse_group(list, " or "),
+            Condition::Not(cond) => {
+                let inner = cond.parse();
+                if inner.is_empty() {
+                    return inner;
+                }
+                let mut query = Query::new("(not ");
+                query.append(inner).push_sql(")");
+                query
+            }
+        }
+    }
+
+    fn parse_group(list: &[Condition], sep: &str) -> Query {
+        let list: Vec<Query> = list
+            .iter()
+            .map(|c| c.parse())
+            .filter(|q| !q.is_empty())
+            .collect();
+        match list.len() {
+            0 => Query::default(),
+            1 => list.into_iter().next().unwrap(),
+            _ => {
+                let mut query = Query::new("(");
+                query.append(Query::join(&list, sep)).push_sql(")");
+                query
+            }
+        }
+    }
+}
+
+/// 条件取反，如 `!Condition::new("name", Operator::Eq, "cat")`
+impl Not for Condition {
+    type Output = Condition;
+
+    fn not(self) -> Self::Output {
+        Condition::Not(Box::new(self))
+    }
+}
+
 /// 条件解析器，支持复杂条件的拼接，通过 and/or 方法来组合条件，通过 parse 来解析最终用于 sql 查询的 where 条件
 #[derive(Debug, Default, Clone)]
 pub struct Matcher {
-    conds_and: Vec<String>,
-    conds_or: Vec<String>,
+    conds_and: Vec<Query>,
+    conds_or: Vec<Query>,
+    // 是否包含已软删除的数据
+    with_deleted: bool,
 }
 
 impl Matcher {
@@ -146,10 +520,22 @@ impl Matcher {
         self.conds_and.is_empty() && self.conds_or.is_empty()
     }
 
-    /// 清空所有已设置的条件
+    /// 清空所有已设置的条件，并恢复为不包含已软删除的数据
     pub fn clear(&mut self) {
         self.conds_and.clear();
         self.conds_or.clear();
+        self.with_deleted = false;
+    }
+
+    /// 包含已软删除的数据，默认只查询、修改未删除的数据，见 Table::soft_delete_column
+    pub fn with_deleted(&mut self) -> &mut Self {
+        self.with_deleted = true;
+        self
+    }
+
+    /// 是否包含已软删除的数据
+    pub fn is_with_deleted(&self) -> bool {
+        self.with_deleted
     }
 
     /// 拼接 and 条件
@@ -161,13 +547,13 @@ impl Matcher {
     /// let mut matcher = Matcher::new();
     /// matcher.and("id", Operator::Eq, 1);
     /// ```
-    pub fn and<T: ToString>(&mut self, key: &str, op: Operator, value: T) -> &Self {
+    pub fn and<T: ToType>(&mut self, key: &str, op: Operator, value: T) -> &Self {
         let cond = Cond::new(key, op, value);
         self.conds_and.push(cond.parse());
         self
     }
 
-    /// 拼接 and 条件
+    /// 拼接 and 条件，值统一按字符串绑定
     /// # Examples
     /// ```
     ///
@@ -177,7 +563,7 @@ impl Matcher {
     /// matcher.and_str("id", Operator::Eq, "value");
     /// ```
     pub fn and_str<T: ToString>(&mut self, key: &str, op: Operator, value: T) -> &Self {
-        let cond = Cond::new(key, op, format!("'{}'", value.to_string()));
+        let cond = Cond::new(key, op, value.to_string());
         self.conds_and.push(cond.parse());
         self
     }
@@ -194,16 +580,56 @@ impl Matcher {
     /// matcher.and("name", Operator::Eq, "cat");
     ///
     /// let mut search = Search::default();
-    /// search.matcher.and("id", Operator::In, "1,2,3,4");
+    /// search.matcher.and("id", Operator::In, vec![1, 2, 3, 4]);
     /// search.matcher.and_matcher(matcher);
     /// ```
     /// 最终的 where 条件如下：
-    /// where id in (1,2,3,4) and (id=1 and name='cat')
+    /// where id in (?,?,?,?) and (id=? and name=?)
     pub fn and_matcher(&mut self, matcher: Self) -> &Self {
         self.conds_and.push(matcher.parse());
         self
     }
 
+    /// 拼接条件树作为 and 条件，条件树为空时忽略
+    pub fn and_cond(&mut self, cond: Condition) -> &Self {
+        let query = cond.parse();
+        if !query.is_empty() {
+            self.conds_and.push(query);
+        }
+        self
+    }
+
+    /// 拼接另一个条件取反后作为 and 条件
+    /// # Examples
+    /// ```
+    /// use lib_sql::utils::{Matcher, Operator};
+    ///
+    /// let mut banned = Matcher::new();
+    /// banned.or("status", Operator::Eq, 2);
+    /// banned.or("email", Operator::IsNull, ());
+    ///
+    /// let mut matcher = Matcher::new();
+    /// matcher.and("age", Operator::Between, [18, 60]);
+    /// matcher.and_not(banned);
+    /// ```
+    /// 最终的 where 条件如下：
+    /// where (age between ? and ?) and (not ((status = ?) or (email is null)))
+    pub fn and_not(&mut self, matcher: Self) -> &Self {
+        if !matcher.is_empty() {
+            let mut query = Query::new("(not ");
+            query.append(matcher.parse()).push_sql(")");
+            self.conds_and.push(query);
+        }
+        self
+    }
+
+    /// 拼接关联数据的条件：P 关联的 C 中存在满足 matcher 的数据，以 exists 子查询实现，
+    /// 不会因一对多产生重复的行。默认排除 C 中已软删除的数据，见 Relation
+    pub fn and_related<P: Table, C: Table>(&mut self, relation: &Relation, matcher: Matcher) -> &Self {
+        self.conds_and.push(relation.exists::<P, C>(&matcher));
+        self
+    }
+
     /// 拼接 or 条件
     /// # Examples
     /// ```
@@ -212,12 +638,21 @@ impl Matcher {
     /// let mut matcher = Matcher::new();
     /// matcher.or("id", Operator::Eq, 1);
     /// ```
-    pub fn or<T: ToString>(&mut self, key: &str, op: Operator, value: T) -> &Self {
+    pub fn or<T: ToType>(&mut self, key: &str, op: Operator, value: T) -> &Self {
         let cond = Cond::new(key, op, value);
         self.conds_or.push(cond.parse());
         self
     }
 
+    /// 拼接条件树作为 or 条件，条件树为空时忽略
+    pub fn or_cond(&mut self, cond: Condition) -> &Self {
+        let query = cond.parse();
+        if !query.is_empty() {
+            self.conds_or.push(query);
+        }
+        self
+    }
+
     /// 拼接另一个 or 条件
     /// # Examples
     /// ```
@@ -229,41 +664,41 @@ impl Matcher {
     /// matcher.and("name", Operator::Eq, "cat");
     ///
     /// let mut search = Search::default();
-    /// search.matcher.and("id", Operator::In, "1,2,3,4");
+    /// search.matcher.and("id", Operator::In, vec![1, 2, 3, 4]);
     /// search.matcher.or_matcher(matcher);
     /// ```
     /// 最终的 where 条件如下：
-    /// where id in (1,2,3,4) or (id=1 and name='cat')
+    /// where id in (?,?,?,?) or (id=? and name=?)
     pub fn or_matcher(&mut self, matcher: Self) -> &Self {
         self.conds_or.push(matcher.parse());
         self
     }
 
-    /// 将条件最终解析为 sql 可用的条件
-    pub(crate) fn parse(&self) -> String {
+    /// 将条件最终解析为 sql 可用的条件及参数
+    pub(crate) fn parse(&self) -> Query {
         if self.is_empty() {
-            return String::new();
+            return Query::default();
         }
+        let and = Query::join(&self.conds_and, " and ");
+        let or = Query::join(&self.conds_or, " or ");
+        let mut query = Query::new("(");
         if !self.conds_and.is_empty() && !self.conds_or.is_empty() {
-            format!(
-                "({}) and ({})",
-                self.conds_and.join(" and "),
-                self.conds_or.join(" or ")
-            )
+            query.append(and).push_sql(") and (").append(or);
+        } else if !self.conds_and.is_empty() {
+            query.append(and);
         } else {
-            if !self.conds_and.is_empty() {
-                format!("({})", self.conds_and.join(" and "),)
-            } else {
-                format!("({})", self.conds_or.join(" or "))
-            }
+            query.append(or);
         }
+        query.push_sql(")");
+        query
     }
 }
 
 /// 排序器，用于组合最终提供给 sql 使用的排序语句
 #[derive(Debug, Default, Clone)]
 pub struct Comparator {
-    list: Vec<String>,
+    // 排序字段及是否倒序
+    list: Vec<(String, bool)>,
 }
 
 impl Comparator {
@@ -286,8 +721,7 @@ impl Comparator {
     /// comp.add("name", false);
     /// ```
     pub fn add(&mut self, key: &str, desc: bool) {
-        self.list
-            .push(format!("{} {}", key, if desc { "desc" } else { "asc" }));
+        self.list.push((key.to_string(), desc));
     }
 
     /// 添加排序
@@ -300,8 +734,7 @@ impl Comparator {
     /// comp.insert(1, "name", false);
     /// ```
     pub fn insert(&mut self, index: usize, key: &str, desc: bool) {
-        self.list
-            .insert(index, format!("{} {}", key, if desc { "desc" } else { "asc" }));
+        self.list.insert(index, (key.to_string(), desc));
     }
 
     /// 清空排序字段
@@ -309,12 +742,22 @@ impl Comparator {
         self.list.clear();
     }
 
+    /// 排序字段及是否倒序
+    pub fn keys(&self) -> &[(String, bool)] {
+        &self.list
+    }
+
     /// 解析出最终 sql 可用的排序语句，如：order by id desc,name asc;
     pub(crate) fn parse(&self) -> String {
         if self.is_empty() {
             return String::new();
         }
-        format!(" order by {}", self.list.join(","))
+        let list: Vec<String> = self
+            .list
+            .iter()
+            .map(|(key, desc)| format!("{} {}", key, if *desc { "desc" } else { "asc" }))
+            .collect();
+        format!(" order by {}", list.join(","))
     }
 }
 
@@ -330,14 +773,29 @@ impl Comparator {
 ///  search.matcher.and("id", Operator::Eq, 1); // 可选，条件，不提供查询条件则默认会查全表
 ///  search.sort.add("id", true); // 可选，排序
 ///  search.group = "name".to_string(); // 可选，group by
-///  search.start = 0;  // 可选，分页
+///  search.start = 0;  // 可选，分页，不指定时从第一条开始
 ///  search.limit = 10; // 可选，一页拿 10 条数据
 ///  // 调用查询接口
 ///  // let list = dao.list(&mut search).unwrap();
-///  // 获取查询的总数，只有指定了分页条件 start & limit 时，total 才会有值返回
+///  // 获取查询的总数，只有指定了分页条件 limit 时，total 才会有值返回
 ///  let total = search.total;
 /// ```
 ///
+/// 数据量较大时可以使用游标分页，按排序字段定位下一页，不使用 offset：
+/// ```
+/// use lib_sql::utils::Search;
+/// let mut search = Search::default();
+/// search.sort.add("created_at", true);
+/// search.limit = 20;
+/// search.skip_total = true; // 不统计总数
+/// // let list = dao.list(&mut search)?;
+/// // 下一页，next_cursor 为空时表示没有更多数据
+/// // if let Some(cursor) = search.next_cursor.clone() {
+/// //     search.after(&cursor);
+/// //     let list = dao.list(&mut search)?;
+/// // }
+/// ```
+///
 #[derive(Debug, Clone)]
 pub struct Search {
     pub matcher: Matcher,
@@ -347,6 +805,12 @@ pub struct Search {
     pub limit: isize,
     pub sort: Comparator,
     pub total: i64,
+    /// 游标分页的位置，由上一页返回的 next_cursor 得到，设置后忽略 start
+    pub cursor: Option<String>,
+    /// 查询后填充的下一页游标，本页不足 limit 条时为空
+    pub next_cursor: Option<String>,
+    /// 不统计总数
+    pub skip_total: bool,
 }
 
 impl Default for Search {
@@ -359,6 +823,9 @@ impl Default for Search {
             limit: -1,
             sort: Default::default(),
             total: Default::default(),
+            cursor: None,
+            next_cursor: None,
+            skip_total: false,
         }
     }
 }
@@ -368,34 +835,52 @@ impl Search {
         Default::default()
     }
     
-    /// 判断是否提供了分页条件
+    /// 判断是否需要统计总数，提供了分页条件 start 与 limit 且未设置 skip_total 时统计
     pub fn is_seach_total(&self) -> bool {
-        self.start > -1 && self.limit > -1
+        !self.skip_total && self.start > -1 && self.limit > -1
+    }
+
+    /// 包含已软删除的数据，见 Matcher::with_deleted
+    pub fn with_deleted(&mut self) -> &mut Self {
+        self.matcher.with_deleted();
+        self
+    }
+
+    /// 只查询关联的 C 中存在满足条件数据的 P，见 Matcher::and_related
+    pub fn with_related<P: Table, C: Table>(&mut self, relation: &Relation, matcher: Matcher) -> &mut Self {
+        self.matcher.and_related::<P, C>(relation, matcher);
+        self
+    }
+
+    /// 从游标之后开始查询下一页，游标为上一次查询返回的 next_cursor
+    pub fn after(&mut self, cursor: &str) -> &mut Self {
+        self.cursor = Some(cursor.to_string());
+        self
     }
 
-    /// 解析出最终 sql 可用的查询语句。如：
-    /// select * from animals where id>0 group by name order by id desc,name asc limit 0,10;
-    pub fn parse(&self, table: &str) -> String {
-        let mut sql = format!("select {} from {}", self.fileds, table);
+    /// 解析出最终 sql 可用的查询语句及参数。如：
+    /// select * from animals where id>? group by name order by id desc,name asc limit 0,10;
+    pub fn parse(&self, table: &str) -> Query {
+        let mut query = Query::new(&format!("select {} from {}", self.fileds, table));
 
         // 条件
         if !self.matcher.is_empty() {
             let cond = self.matcher.parse();
             if !cond.is_empty() {
-                sql.push_str(&format!(" where {}", cond));
+                query.push_sql(" where ").append(cond);
             }
         }
 
         if !self.group.is_empty() {
-            sql.push_str(" group by ");
-            sql.push_str(&self.group);
+            query.push_sql(" group by ");
+            query.push_sql(&self.group);
         }
         if !self.sort.is_empty() {
-            sql.push_str(&self.sort.parse());
+            query.push_sql(&self.sort.parse());
         }
         if self.start > -1 && self.limit > -1 {
-            sql.push_str(&format! {" limit {},{}", self.start, self.limit});
+            query.push_sql(&format!(" limit {},{}", self.start, self.limit));
         }
-        sql
+        query
     }
 }